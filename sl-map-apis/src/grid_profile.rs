@@ -0,0 +1,132 @@
+//! Contains the URLs of the services of a grid so the other modules can be
+//! used with grids other than the Second Life main grid
+//! (e.g. the beta grid or OpenSim grids)
+
+use sl_types::map::{GridCoordinates, GridRectangle, GridRectangleLike as _};
+
+/// the base URL of the Second Life main map tile server
+pub const SECOND_LIFE_MAIN_MAP_TILE_BASE_URL: &str = "https://secondlife-maps-cdn.akamaized.net";
+
+/// the URL of the Second Life main grid capability used to look up
+/// the grid coordinates for a region name
+pub const SECOND_LIFE_MAIN_MAP_BLOCK_QUERY_URL: &str =
+    "https://cap.secondlife.com/cap/0/d661249b-2b5a-4436-966a-3d3b8d7a574f";
+
+/// the URL of the Second Life main grid capability used to look up
+/// the region name for grid coordinates
+pub const SECOND_LIFE_MAIN_NAME_SERVICE_URL: &str =
+    "https://cap.secondlife.com/cap/0/b713fe80-283b-4585-af4d-a3b7d9a32492";
+
+/// the URLs of the map related services of a grid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridProfile {
+    /// the base URL of the map tile server (without a trailing slash)
+    map_tile_base_url: String,
+    /// the URL used to look up the grid coordinates for a region name
+    map_block_query_url: String,
+    /// the URL used to look up the region name for grid coordinates
+    name_service_url: String,
+    /// the area of the grid in which regions can plausibly exist, `None`
+    /// if regions can exist anywhere
+    plausible_region_bounds: Option<GridRectangle>,
+}
+
+impl GridProfile {
+    /// creates a new `GridProfile`
+    ///
+    /// # Arguments
+    ///
+    /// * `map_tile_base_url` - the base URL of the map tile server (without a trailing slash)
+    /// * `map_block_query_url` - the URL used to look up the grid coordinates for a region name
+    /// * `name_service_url` - the URL used to look up the region name for grid coordinates
+    #[must_use]
+    pub fn new(
+        map_tile_base_url: impl Into<String>,
+        map_block_query_url: impl Into<String>,
+        name_service_url: impl Into<String>,
+    ) -> Self {
+        Self {
+            map_tile_base_url: map_tile_base_url.into(),
+            map_block_query_url: map_block_query_url.into(),
+            name_service_url: name_service_url.into(),
+            plausible_region_bounds: None,
+        }
+    }
+
+    /// sets the area of the grid in which regions can plausibly exist,
+    /// lookups for grid coordinates outside of it are skipped
+    #[must_use]
+    pub fn with_plausible_region_bounds(mut self, plausible_region_bounds: GridRectangle) -> Self {
+        self.plausible_region_bounds = Some(plausible_region_bounds);
+        self
+    }
+
+    /// the `GridProfile` of the Second Life main grid (agni)
+    #[must_use]
+    pub fn second_life_main() -> Self {
+        Self::new(
+            SECOND_LIFE_MAIN_MAP_TILE_BASE_URL,
+            SECOND_LIFE_MAIN_MAP_BLOCK_QUERY_URL,
+            SECOND_LIFE_MAIN_NAME_SERVICE_URL,
+        )
+        .with_plausible_region_bounds(GridRectangle::new(
+            GridCoordinates::new(395, 479),
+            GridCoordinates::new(1358, 1430),
+        ))
+    }
+
+    /// the base URL of the map tile server (without a trailing slash)
+    #[must_use]
+    pub fn map_tile_base_url(&self) -> &str {
+        &self.map_tile_base_url
+    }
+
+    /// the URL used to look up the grid coordinates for a region name
+    #[must_use]
+    pub fn map_block_query_url(&self) -> &str {
+        &self.map_block_query_url
+    }
+
+    /// the URL used to look up the region name for grid coordinates
+    #[must_use]
+    pub fn name_service_url(&self) -> &str {
+        &self.name_service_url
+    }
+
+    /// the area of the grid in which regions can plausibly exist, `None`
+    /// if regions can exist anywhere
+    #[must_use]
+    pub fn plausible_region_bounds(&self) -> Option<&GridRectangle> {
+        self.plausible_region_bounds.as_ref()
+    }
+
+    /// a cheap check if a region could plausibly exist at these grid
+    /// coordinates on this grid, it does not mean there is a region there
+    #[must_use]
+    pub fn is_plausible_region(&self, grid_coordinates: &GridCoordinates) -> bool {
+        self.plausible_region_bounds
+            .as_ref()
+            .is_none_or(|bounds| bounds.contains(grid_coordinates))
+    }
+}
+
+impl Default for GridProfile {
+    fn default() -> Self {
+        Self::second_life_main()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_plausible_region() {
+        let second_life_main = GridProfile::second_life_main();
+        assert!(second_life_main.is_plausible_region(&GridCoordinates::new(1136, 1075)));
+        assert!(!second_life_main.is_plausible_region(&GridCoordinates::new(10, 10)));
+        let custom = GridProfile::new("http://a", "http://b", "http://c");
+        assert!(custom.is_plausible_region(&GridCoordinates::new(10, 10)));
+        assert!(custom.is_plausible_region(&GridCoordinates::new(1136, 30000)));
+    }
+}
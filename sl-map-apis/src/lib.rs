@@ -1,4 +1,6 @@
 #![doc = include_str!("../README.md")]
 
+pub mod grid_profile;
 pub mod map_tiles;
+#[cfg(feature = "network")]
 pub mod region;
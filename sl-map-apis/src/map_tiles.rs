@@ -1,13 +1,99 @@
 //! Contains functionality related to fetching map tiles
+#[cfg(feature = "network")]
 use std::path::PathBuf;
 
 use image::GenericImageView as _;
 use sl_types::map::{
     GridCoordinateOffset, GridCoordinates, GridRectangle, GridRectangleLike, MapTileDescriptor,
-    RegionCoordinates, RegionName, USBNotecard, ZoomFitError, ZoomLevel, ZoomLevelError,
+    RegionCoordinates, RegionName, USBWaypoint, ZoomFitError, ZoomLevel,
 };
+#[cfg(feature = "network")]
+use sl_types::map::{GridCoordinatesExt as _, MapLayer, USBNotecard, ZoomLevelError};
 
-use crate::region::RegionNameToGridCoordinatesCache;
+#[cfg(feature = "network")]
+use crate::grid_profile::GridProfile;
+#[cfg(feature = "network")]
+use crate::region::{RegionLookupBackend, RegionNameToGridCoordinatesCache};
+
+/// a corner of a map, used to position overlays like the scale bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    /// the upper left corner
+    UpperLeft,
+    /// the upper right corner
+    UpperRight,
+    /// the lower left corner
+    LowerLeft,
+    /// the lower right corner
+    LowerRight,
+}
+
+impl Corner {
+    /// the distance in pixels between overlays and the edges of the image
+    const MARGIN: u32 = 10;
+
+    /// the pixel coordinates of the upper left corner of an overlay of the
+    /// given size placed in this corner of an image of the given size
+    #[must_use]
+    fn overlay_origin(
+        &self,
+        (image_width, image_height): (u32, u32),
+        (overlay_width, overlay_height): (u32, u32),
+    ) -> (i32, i32) {
+        let left = Self::MARGIN as i32;
+        let right = image_width as i32 - Self::MARGIN as i32 - overlay_width as i32;
+        let top = Self::MARGIN as i32;
+        let bottom = image_height as i32 - Self::MARGIN as i32 - overlay_height as i32;
+        match self {
+            Self::UpperLeft => (left, top),
+            Self::UpperRight => (right, top),
+            Self::LowerLeft => (left, bottom),
+            Self::LowerRight => (right, bottom),
+        }
+    }
+}
+
+/// the style used to draw lines and routes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineStyle {
+    /// a continuous line
+    #[default]
+    Solid,
+    /// a dashed line, e.g. to distinguish planned routes from actual ones
+    Dashed {
+        /// the length of each dash in pixels
+        dash: f32,
+        /// the length of the gaps between the dashes in pixels
+        gap: f32,
+    },
+}
+
+impl LineStyle {
+    /// the dash and gap lengths if this is a dashed style with a positive
+    /// dash and gap length, other dashed styles are drawn as solid lines
+    #[must_use]
+    fn dash_and_gap(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::Solid => None,
+            Self::Dashed { dash, gap } if *dash > 0f32 && *gap > 0f32 => Some((*dash, *gap)),
+            Self::Dashed { .. } => None,
+        }
+    }
+
+    /// is the line drawn at the given distance in pixels from its start
+    #[must_use]
+    fn is_drawn_at(&self, distance: f32) -> bool {
+        match self.dash_and_gap() {
+            None => true,
+            Some((dash, gap)) => distance.rem_euclid(dash + gap) < dash,
+        }
+    }
+}
+
+/// the distances in meters that are considered for the length of a scale bar
+const SCALE_BAR_DISTANCES: [u32; 16] = [
+    1, 2, 5, 10, 20, 50, 100, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536,
+];
 
 /// represents a map like image, e.g. a map tile or a map that covers
 /// some `GridRectangle` of regions
@@ -56,6 +142,26 @@ pub trait MapLike: GridRectangleLike + image::GenericImage + image::GenericImage
         Some((x, y))
     }
 
+    /// the pixel coordinates in the map that represent the given `GridCoordinates`
+    /// and `RegionCoordinates` without truncating them to whole pixels
+    #[must_use]
+    fn pixel_coordinates_for_coordinates_f32(
+        &self,
+        grid_coordinates: &GridCoordinates,
+        region_coordinates: &RegionCoordinates,
+    ) -> Option<(f32, f32)> {
+        if !self.contains(grid_coordinates) {
+            return None;
+        }
+        let grid_offset = *grid_coordinates - self.lower_left_corner();
+        let x = self.pixels_per_region() * grid_offset.x() as f32
+            + self.pixels_per_meter() * region_coordinates.x();
+        let y = self.pixels_per_region() * grid_offset.y() as f32
+            + self.pixels_per_meter() * region_coordinates.y();
+        let y = self.height() as f32 - y;
+        Some((x, y))
+    }
+
     /// the `GridCoordinates` and `RegionCoordinates` at the given pixel coordinates
     #[must_use]
     fn coordinates_for_pixel_coordinates(
@@ -80,6 +186,47 @@ pub trait MapLike: GridRectangleLike + image::GenericImage + image::GenericImage
         Some((grid_result, region_result))
     }
 
+    /// the `GridCoordinates` of the region at the given pixel coordinates
+    ///
+    /// returns `None` if the pixel coordinates are outside of the map
+    #[must_use]
+    fn region_at_pixel(&self, x: u32, y: u32) -> Option<GridCoordinates> {
+        let (width, height) = self.dimensions();
+        if x >= width || y >= height {
+            return None;
+        }
+        // pixel rows are counted from the top, the bottom row is the first
+        // row of the lowest regions
+        let y = height - 1 - y;
+        let grid_coordinates = self.lower_left_corner()
+            + GridCoordinateOffset::new(
+                (x as f32 / self.pixels_per_region()) as i32,
+                (y as f32 / self.pixels_per_region()) as i32,
+            );
+        if !self.contains(&grid_coordinates) {
+            return None;
+        }
+        Some(grid_coordinates)
+    }
+
+    /// converts pixel coordinates on this map to the pixel coordinates of the
+    /// same position on another map, e.g. from a click on an overview map to
+    /// a detail map of the same area at a higher zoom level
+    ///
+    /// returns `None` if the position is outside of either map
+    #[must_use]
+    fn rescale_pixel(&self, x: u32, y: u32, target: &impl MapLike) -> Option<(u32, u32)>
+    where
+        Self: Sized,
+    {
+        let (grid_coordinates, region_coordinates) =
+            self.coordinates_for_pixel_coordinates(x, y)?;
+        if !self.contains(&grid_coordinates) {
+            return None;
+        }
+        target.pixel_coordinates_for_coordinates(&grid_coordinates, &region_coordinates)
+    }
+
     /// a crop of the map like image by coordinates and size
     #[must_use]
     fn crop_imm_grid_rectangle(
@@ -110,9 +257,15 @@ pub trait MapLike: GridRectangleLike + image::GenericImage + image::GenericImage
 
     /// draw a waypoint at the given coordinates
     fn draw_waypoint(&mut self, x: u32, y: u32, color: image::Rgba<u8>) {
+        self.draw_waypoint_f32(x as f32, y as f32, color);
+    }
+
+    /// draw a waypoint at the given sub-pixel coordinates
+    fn draw_waypoint_f32(&mut self, x: f32, y: f32, color: image::Rgba<u8>) {
         imageproc::drawing::draw_filled_rect_mut(
             self.image_mut(),
-            imageproc::rect::Rect::at(x as i32 - 5i32, y as i32 - 5i32).of_size(10, 10),
+            imageproc::rect::Rect::at((x - 5f32).round() as i32, (y - 5f32).round() as i32)
+                .of_size(10, 10),
             color,
         );
     }
@@ -126,30 +279,57 @@ pub trait MapLike: GridRectangleLike + image::GenericImage + image::GenericImage
         to_y: u32,
         color: image::Rgba<u8>,
     ) {
-        let from_x = from_x as f32;
-        let from_y = from_y as f32;
-        let to_x = to_x as f32;
-        let to_y = to_y as f32;
+        self.draw_line_f32(
+            (from_x as f32, from_y as f32),
+            (to_x as f32, to_y as f32),
+            color,
+        );
+    }
+
+    /// draw a line from the given sub-pixel coordinates to the given
+    /// sub-pixel coordinates
+    fn draw_line_f32(
+        &mut self,
+        (from_x, from_y): (f32, f32),
+        (to_x, to_y): (f32, f32),
+        color: image::Rgba<u8>,
+    ) {
+        // clip the line to the image (plus the half width of the line) so
+        // lines to points outside of the map end cleanly at the border
+        let (width, height) = self.dimensions();
+        let Some(((from_x, from_y), (to_x, to_y))) = clip_line(
+            (from_x, from_y),
+            (to_x, to_y),
+            (-5f32, -5f32),
+            (width as f32 + 5f32, height as f32 + 5f32),
+        ) else {
+            return;
+        };
         let diff = (to_x - from_x, to_y - from_y);
         let perpendicular = (-diff.1, diff.0);
         let magnitude = (diff.0.powi(2) + diff.1.powi(2)).sqrt();
+        if magnitude < f32::EPSILON {
+            // a line without length (e.g. one only touching a corner of the
+            // clip area) has no direction to draw the width of the line in
+            return;
+        }
         let perpendicular_normalized = (perpendicular.0 / magnitude, perpendicular.1 / magnitude);
         let points = vec![
             imageproc::point::Point::new(
-                (from_x + perpendicular_normalized.0 * 5.0) as i32,
-                (from_y + perpendicular_normalized.1 * 5.0) as i32,
+                (from_x + perpendicular_normalized.0 * 5.0).round() as i32,
+                (from_y + perpendicular_normalized.1 * 5.0).round() as i32,
             ),
             imageproc::point::Point::new(
-                (to_x + perpendicular_normalized.0 * 5.0) as i32,
-                (to_y + perpendicular_normalized.1 * 5.0) as i32,
+                (to_x + perpendicular_normalized.0 * 5.0).round() as i32,
+                (to_y + perpendicular_normalized.1 * 5.0).round() as i32,
             ),
             imageproc::point::Point::new(
-                (to_x - perpendicular_normalized.0 * 5.0) as i32,
-                (to_y - perpendicular_normalized.1 * 5.0) as i32,
+                (to_x - perpendicular_normalized.0 * 5.0).round() as i32,
+                (to_y - perpendicular_normalized.1 * 5.0).round() as i32,
             ),
             imageproc::point::Point::new(
-                (from_x - perpendicular_normalized.0 * 5.0) as i32,
-                (from_y - perpendicular_normalized.1 * 5.0) as i32,
+                (from_x - perpendicular_normalized.0 * 5.0).round() as i32,
+                (from_y - perpendicular_normalized.1 * 5.0).round() as i32,
             ),
         ];
         imageproc::drawing::draw_antialiased_polygon_mut(
@@ -160,6 +340,222 @@ pub trait MapLike: GridRectangleLike + image::GenericImage + image::GenericImage
         );
     }
 
+    /// blends the given color over the pixels in the given rectangle,
+    /// honoring the alpha channel of the color, parts of the rectangle
+    /// outside of the image are ignored
+    fn blend_rect(&mut self, rect: imageproc::rect::Rect, color: image::Rgba<u8>) {
+        let (width, height) = self.image().dimensions();
+        let map_image = self.image_mut();
+        for y in rect.top().max(0)..=rect.bottom().min(height as i32 - 1) {
+            for x in rect.left().max(0)..=rect.right().min(width as i32 - 1) {
+                let mut pixel = map_image.get_pixel(x as u32, y as u32);
+                image::Pixel::blend(&mut pixel, &color);
+                image::GenericImage::put_pixel(map_image, x as u32, y as u32, pixel);
+            }
+        }
+    }
+
+    /// blends the given color over the pixels in the circle with the given
+    /// center and radius, honoring the alpha channel of the color, parts of
+    /// the circle outside of the image are ignored
+    fn blend_filled_circle(&mut self, center: (i32, i32), radius: i32, color: image::Rgba<u8>) {
+        let (width, height) = self.image().dimensions();
+        let map_image = self.image_mut();
+        for y in (center.1 - radius).max(0)..=(center.1 + radius).min(height as i32 - 1) {
+            for x in (center.0 - radius).max(0)..=(center.0 + radius).min(width as i32 - 1) {
+                if (x - center.0).pow(2) + (y - center.1).pow(2) > radius.pow(2) {
+                    continue;
+                }
+                let mut pixel = map_image.get_pixel(x as u32, y as u32);
+                image::Pixel::blend(&mut pixel, &color);
+                image::GenericImage::put_pixel(map_image, x as u32, y as u32, pixel);
+            }
+        }
+    }
+
+    /// draw a border of the given thickness along the inside of the edges
+    /// of the given `GridRectangle`, e.g. to highlight a region in a larger
+    /// map, parts of the border outside of the image are ignored
+    fn draw_border(
+        &mut self,
+        grid_rectangle: &GridRectangle,
+        color: image::Rgba<u8>,
+        thickness: u32,
+    ) {
+        if thickness == 0 {
+            return;
+        }
+        let pixels_per_region = self.pixels_per_region();
+        let left = (pixels_per_region
+            * (i32::from(grid_rectangle.lower_left_corner().x())
+                - i32::from(self.lower_left_corner().x())) as f32)
+            .round() as i32;
+        let bottom = self.height() as i32
+            - (pixels_per_region
+                * (i32::from(grid_rectangle.lower_left_corner().y())
+                    - i32::from(self.lower_left_corner().y())) as f32)
+                .round() as i32;
+        let width = (pixels_per_region * f32::from(grid_rectangle.size_x())).round() as u32;
+        let height = (pixels_per_region * f32::from(grid_rectangle.size_y())).round() as u32;
+        if width == 0 || height == 0 {
+            return;
+        }
+        let top = bottom - height as i32;
+        let thickness_x = thickness.min(width);
+        let thickness_y = thickness.min(height);
+        for rect in [
+            imageproc::rect::Rect::at(left, top).of_size(width, thickness_y),
+            imageproc::rect::Rect::at(left, bottom - thickness_y as i32)
+                .of_size(width, thickness_y),
+            imageproc::rect::Rect::at(left, top).of_size(thickness_x, height),
+            imageproc::rect::Rect::at(left + width as i32 - thickness_x as i32, top)
+                .of_size(thickness_x, height),
+        ] {
+            imageproc::drawing::draw_filled_rect_mut(self.image_mut(), rect, color);
+        }
+    }
+
+    /// draw thin lines along all the region boundaries on the map
+    fn draw_grid_lines(&mut self, color: image::Rgba<u8>) {
+        let (width, height) = self.dimensions();
+        if width == 0 || height == 0 {
+            return;
+        }
+        let pixels_per_region = self.pixels_per_region();
+        for i in 0..=self.size_x() {
+            let x = ((pixels_per_region * f32::from(i)).round() as u32).min(width - 1);
+            imageproc::drawing::draw_filled_rect_mut(
+                self.image_mut(),
+                imageproc::rect::Rect::at(x as i32, 0).of_size(1, height),
+                color,
+            );
+        }
+        for i in 0..=self.size_y() {
+            let y = height
+                .saturating_sub((pixels_per_region * f32::from(i)).round() as u32)
+                .min(height - 1);
+            imageproc::drawing::draw_filled_rect_mut(
+                self.image_mut(),
+                imageproc::rect::Rect::at(0, y as i32).of_size(width, 1),
+                color,
+            );
+        }
+    }
+
+    /// draw a line from the given sub-pixel coordinates to the given
+    /// sub-pixel coordinates in the given style
+    fn draw_line_styled(
+        &mut self,
+        from: (f32, f32),
+        to: (f32, f32),
+        color: image::Rgba<u8>,
+        style: LineStyle,
+    ) {
+        let Some((dash, gap)) = style.dash_and_gap() else {
+            self.draw_line_f32(from, to, color);
+            return;
+        };
+        let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let point_at = |distance: f32| {
+            (
+                from.0 + (to.0 - from.0) * distance / length,
+                from.1 + (to.1 - from.1) * distance / length,
+            )
+        };
+        let mut dash_start = 0f32;
+        while dash_start < length {
+            let dash_end = (dash_start + dash).min(length);
+            self.draw_line_f32(point_at(dash_start), point_at(dash_end), color);
+            dash_start = dash_end + gap;
+        }
+    }
+
+    /// draw a scale bar in the given corner of the map
+    ///
+    /// the length of the scale bar is a round distance in meters that
+    /// takes up at most a quarter of the width of the map, the label with
+    /// the distance is only drawn if a font is given
+    fn draw_scale_bar<F: ab_glyph::Font>(
+        &mut self,
+        position: Corner,
+        color: image::Rgba<u8>,
+        font: Option<&F>,
+    ) where
+        Self: Sized,
+    {
+        /// the height of the bar itself in pixels
+        const BAR_HEIGHT: u32 = 4;
+        /// the height of the ticks at both ends of the bar in pixels
+        const TICK_HEIGHT: u32 = 10;
+        /// the font size of the label in pixels
+        const LABEL_SCALE: f32 = 14f32;
+        let max_length = self.width() as f32 / 4f32;
+        let meters = SCALE_BAR_DISTANCES
+            .iter()
+            .rev()
+            .find(|meters| **meters as f32 * self.pixels_per_meter() <= max_length)
+            .unwrap_or(&SCALE_BAR_DISTANCES[0]);
+        let label = format!("{meters} m");
+        let bar_width = ((*meters as f32 * self.pixels_per_meter()).round() as u32).max(2);
+        // the height of the text itself does not include the space between
+        // the top of the line and the top of the glyphs so we use the line
+        // height to avoid drawing the bar over the label
+        let (label_width, label_height) = match font {
+            Some(font) => (
+                imageproc::drawing::text_size(LABEL_SCALE, font, &label).0,
+                ab_glyph::ScaleFont::height(&font.as_scaled(LABEL_SCALE)).ceil() as u32,
+            ),
+            None => (0, 0),
+        };
+        let overlay_size = (bar_width.max(label_width), label_height + TICK_HEIGHT);
+        let (x, y) = position.overlay_origin(self.dimensions(), overlay_size);
+        if let Some(font) = font {
+            imageproc::drawing::draw_text_mut(
+                self.image_mut(),
+                color,
+                x,
+                y,
+                LABEL_SCALE,
+                font,
+                &label,
+            );
+        }
+        let bar_top = y + label_height as i32;
+        imageproc::drawing::draw_filled_rect_mut(
+            self.image_mut(),
+            imageproc::rect::Rect::at(x, bar_top + (TICK_HEIGHT - BAR_HEIGHT) as i32)
+                .of_size(bar_width, BAR_HEIGHT),
+            color,
+        );
+        for tick_x in [x, x + bar_width as i32 - 2] {
+            imageproc::drawing::draw_filled_rect_mut(
+                self.image_mut(),
+                imageproc::rect::Rect::at(tick_x, bar_top).of_size(2, TICK_HEIGHT),
+                color,
+            );
+        }
+    }
+
+    /// draw an arrow pointing north in the given corner of the map
+    ///
+    /// Second Life maps are always north-up so the arrow always points
+    /// to the top of the image
+    fn draw_north_arrow(&mut self, position: Corner, size: u32, color: image::Rgba<u8>) {
+        let size = size.max(4);
+        let (x, y) = position.overlay_origin(self.dimensions(), (size, size));
+        let size = size as i32;
+        imageproc::drawing::draw_polygon_mut(
+            self.image_mut(),
+            &[
+                imageproc::point::Point::new(x + size / 2, y),
+                imageproc::point::Point::new(x + size - 1, y + size - 1),
+                imageproc::point::Point::new(x + size / 2, y + (size * 3) / 4),
+                imageproc::point::Point::new(x, y + size - 1),
+            ],
+            color,
+        );
+    }
+
     /// draw an arrow from the direction of the first point with the
     /// tip at the second point
     fn draw_arrow(&mut self, from: (f32, f32), tip: (f32, f32), color: image::Rgba<u8>) {
@@ -199,6 +595,77 @@ pub trait MapLike: GridRectangleLike + image::GenericImage + image::GenericImage
     }
 }
 
+/// finds the waypoint closest to the given pixel coordinates in a route
+/// previously resolved to pixel coordinates with `Map::route_pixels`
+///
+/// waypoints outside of the map are ignored
+///
+/// returns the waypoint and its distance from the given pixel coordinates
+/// in pixels or `None` if there are no waypoints on the map
+#[must_use]
+pub fn nearest_waypoint_to_pixel(
+    route_pixels: &[(USBWaypoint, Option<(u32, u32)>)],
+    x: u32,
+    y: u32,
+) -> Option<(&USBWaypoint, f32)> {
+    route_pixels
+        .iter()
+        .filter_map(|(waypoint, pixel_coordinates)| {
+            let (waypoint_x, waypoint_y) = (*pixel_coordinates)?;
+            let distance = ((waypoint_x as f32 - x as f32).powi(2)
+                + (waypoint_y as f32 - y as f32).powi(2))
+            .sqrt();
+            Some((waypoint, distance))
+        })
+        .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+}
+
+/// clips the line between the two points to the rectangle between the given
+/// minimum and maximum corners using the Liang-Barsky algorithm
+///
+/// returns `None` if the line is completely outside of the rectangle
+#[must_use]
+fn clip_line(
+    from: (f32, f32),
+    to: (f32, f32),
+    min: (f32, f32),
+    max: (f32, f32),
+) -> Option<((f32, f32), (f32, f32))> {
+    let (diff_x, diff_y) = (to.0 - from.0, to.1 - from.1);
+    let mut start = 0f32;
+    let mut end = 1f32;
+    for (direction, distance) in [
+        (-diff_x, from.0 - min.0),
+        (diff_x, max.0 - from.0),
+        (-diff_y, from.1 - min.1),
+        (diff_y, max.1 - from.1),
+    ] {
+        if direction == 0f32 {
+            // parallel to this edge
+            if distance < 0f32 {
+                return None;
+            }
+        } else {
+            let ratio = distance / direction;
+            if direction < 0f32 {
+                if ratio > end {
+                    return None;
+                }
+                start = start.max(ratio);
+            } else {
+                if ratio < start {
+                    return None;
+                }
+                end = end.min(ratio);
+            }
+        }
+    }
+    Some((
+        (from.0 + start * diff_x, from.1 + start * diff_y),
+        (from.0 + end * diff_x, from.1 + end * diff_y),
+    ))
+}
+
 /// represents a map tile fetched from the server
 #[derive(Debug, Clone)]
 pub struct MapTile {
@@ -265,7 +732,21 @@ impl MapLike for MapTile {
     }
 }
 
+/// the URL of a map tile on the map server with the given base URL
+/// (without a trailing slash), e.g. `GridProfile::map_tile_base_url`
+///
+/// this can be used by tools that want to fetch map tiles themselves
+#[must_use]
+pub fn map_tile_url(map_tile_base_url: &str, map_tile_descriptor: &MapTileDescriptor) -> String {
+    format!(
+        "{}/{}",
+        map_tile_base_url,
+        map_tile_descriptor.tile_filename()
+    )
+}
+
 /// errors that can happen while fetching a map tile from the cache
+#[cfg(feature = "network")]
 #[derive(Debug, thiserror::Error)]
 pub enum MapTileCacheError {
     /// error manipulating files in the cache directory
@@ -302,24 +783,193 @@ pub enum MapTileCacheError {
     /// existed on disk
     #[error("error when trying to load cache policy that we previously checked existed on disk")]
     CachePolicyError,
+    /// the server responded with a content type that is not an image,
+    /// e.g. an HTML error page from the CDN
+    #[error("the server responded with unexpected content type {0:?} instead of an image")]
+    UnexpectedContentType(String),
+    /// the server responded without a content type and the response body
+    /// is not in any image format we recognize
+    #[error("the server responded without a content type and the body is not a recognized image")]
+    UnrecognizedImageData,
 }
 
+/// saves an image as a JPEG file with the given quality (1-100)
+///
+/// # Errors
+///
+/// returns an error if the file could not be created or the image could not
+/// be encoded
+fn save_jpeg(
+    image: &image::DynamicImage,
+    path: &std::path::Path,
+    quality: u8,
+) -> Result<(), image::ImageError> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(file, quality);
+    // JPEG does not support an alpha channel
+    encoder.encode_image(&image.to_rgb8())
+}
+
+/// the HTTP status codes the Second Life main map tile server uses to
+/// indicate that a map tile does not exist
+#[cfg(feature = "network")]
+pub const DEFAULT_ABSENT_STATUS_CODES: &[reqwest::StatusCode] = &[reqwest::StatusCode::FORBIDDEN];
+
 /// a cache for map tiles on the local filesystem
+#[cfg(feature = "network")]
 #[derive(derive_more::Debug)]
 pub struct MapTileCache {
     /// the client used to make HTTP requests for map tiles not in the local cache
     client: reqwest::Client,
     /// the rate limiter for map tile requests to the server
     #[debug(skip)]
-    ratelimiter: Option<ratelimit::Ratelimiter>,
-    /// the cache directory
-    cache_directory: PathBuf,
+    ratelimiter: Option<std::sync::Arc<ratelimit::Ratelimiter>>,
+    /// the URLs of the services of the grid the map tiles are fetched from
+    grid_profile: GridProfile,
+    /// the HTTP status codes the map tile server uses to indicate that
+    /// a map tile does not exist
+    absent_status_codes: Vec<reqwest::StatusCode>,
+    /// the minimum duration cached map tiles are considered fresh for
+    /// regardless of the cache policy sent by the server
+    min_fresh: Option<std::time::Duration>,
+    /// the cache directory, `None` if map tiles are only cached in memory
+    cache_directory: Option<PathBuf>,
     /// the in-memory cache
     #[debug(skip)]
     cache: lru::LruCache<MapTileDescriptor, (Option<MapTile>, http_cache_semantics::CachePolicy)>,
 }
 
+/// errors that can happen when building a `MapTileCache` with a
+/// `MapTileCacheBuilder`
+#[cfg(feature = "network")]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MapTileCacheBuilderError {
+    /// the capacity of the in-memory cache can not be zero
+    #[error("the capacity of the in-memory map tile cache can not be zero")]
+    ZeroMemoryCacheCapacity,
+    /// at least one status code indicating absent map tiles is required
+    #[error("at least one HTTP status code indicating absent map tiles is required")]
+    NoAbsentStatusCodes,
+}
+
+/// a builder for `MapTileCache` for the less common options
+///
+/// options that are not set use the same defaults as `MapTileCache::new`
+#[cfg(feature = "network")]
+#[derive(derive_more::Debug, Default)]
+pub struct MapTileCacheBuilder {
+    /// the cache directory, `None` to only cache in memory
+    cache_directory: Option<PathBuf>,
+    /// the rate limiter for map tile requests to the server
+    #[debug(skip)]
+    ratelimiter: Option<ratelimit::Ratelimiter>,
+    /// the client used to make HTTP requests
+    client: Option<reqwest::Client>,
+    /// the URLs of the services of the grid
+    grid_profile: Option<GridProfile>,
+    /// the HTTP status codes indicating absent map tiles
+    absent_status_codes: Option<Vec<reqwest::StatusCode>>,
+    /// the minimum duration cached map tiles are considered fresh for
+    min_fresh: Option<std::time::Duration>,
+    /// the number of map tiles kept in the in-memory cache
+    memory_cache_capacity: Option<usize>,
+}
+
+#[cfg(feature = "network")]
+impl MapTileCacheBuilder {
+    /// sets the cache directory, without one map tiles are only cached in memory
+    #[must_use]
+    pub fn cache_directory(mut self, cache_directory: PathBuf) -> Self {
+        self.cache_directory = Some(cache_directory);
+        self
+    }
+
+    /// sets the rate limiter for map tile requests to the server
+    #[must_use]
+    pub fn ratelimiter(mut self, ratelimiter: ratelimit::Ratelimiter) -> Self {
+        self.ratelimiter = Some(ratelimiter);
+        self
+    }
+
+    /// sets the client used to make HTTP requests, e.g. to configure
+    /// timeouts, proxies or the user agent
+    #[must_use]
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// sets the `GridProfile` of the grid to fetch map tiles from
+    ///
+    /// see `MapTileCache::with_grid_profile`
+    #[must_use]
+    pub fn grid_profile(mut self, grid_profile: GridProfile) -> Self {
+        self.grid_profile = Some(grid_profile);
+        self
+    }
+
+    /// sets the HTTP status codes that indicate that a map tile does not exist
+    ///
+    /// see `MapTileCache::with_absent_status_codes`
+    #[must_use]
+    pub fn absent_status_codes(
+        mut self,
+        absent_status_codes: impl IntoIterator<Item = reqwest::StatusCode>,
+    ) -> Self {
+        self.absent_status_codes = Some(absent_status_codes.into_iter().collect());
+        self
+    }
+
+    /// sets a minimum duration for which cached map tiles are considered fresh
+    ///
+    /// see `MapTileCache::with_min_fresh`
+    #[must_use]
+    pub fn min_fresh(mut self, min_fresh: std::time::Duration) -> Self {
+        self.min_fresh = Some(min_fresh);
+        self
+    }
+
+    /// sets the number of decoded map tiles kept in the in-memory cache
+    /// (defaults to 16)
+    #[must_use]
+    pub fn memory_cache_capacity(mut self, memory_cache_capacity: usize) -> Self {
+        self.memory_cache_capacity = Some(memory_cache_capacity);
+        self
+    }
+
+    /// builds the `MapTileCache`
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the options are invalid, e.g. a memory
+    /// cache capacity of zero
+    pub fn build(self) -> Result<MapTileCache, MapTileCacheBuilderError> {
+        let mut map_tile_cache =
+            MapTileCache::with_optional_cache_directory(self.cache_directory, self.ratelimiter);
+        if let Some(memory_cache_capacity) = self.memory_cache_capacity {
+            let memory_cache_capacity = std::num::NonZeroUsize::new(memory_cache_capacity)
+                .ok_or(MapTileCacheBuilderError::ZeroMemoryCacheCapacity)?;
+            map_tile_cache.cache = lru::LruCache::new(memory_cache_capacity);
+        }
+        if let Some(absent_status_codes) = self.absent_status_codes {
+            if absent_status_codes.is_empty() {
+                return Err(MapTileCacheBuilderError::NoAbsentStatusCodes);
+            }
+            map_tile_cache.absent_status_codes = absent_status_codes;
+        }
+        if let Some(client) = self.client {
+            map_tile_cache.client = client;
+        }
+        if let Some(grid_profile) = self.grid_profile {
+            map_tile_cache.grid_profile = grid_profile;
+        }
+        map_tile_cache.min_fresh = self.min_fresh;
+        Ok(map_tile_cache)
+    }
+}
+
 /// status of a cache entry on disk
+#[cfg(feature = "network")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MapTileCacheEntryStatus {
     /// no files at all related to a map tile in the cache
@@ -330,17 +980,183 @@ pub enum MapTileCacheEntryStatus {
     Valid,
 }
 
-/// a wrapper around response to force status from 403 to 404 for absent map
-/// tiles so `http_cache_semantics::CachePolicy` becomes usable on those responses
+/// checks if a cached response is fresh according to its cache policy or
+/// because it is younger than the given minimum freshness duration
+#[cfg(feature = "network")]
+#[must_use]
+fn is_fresh(
+    cache_policy: &http_cache_semantics::CachePolicy,
+    request: &reqwest::Request,
+    now: std::time::SystemTime,
+    min_fresh: Option<std::time::Duration>,
+) -> bool {
+    if let http_cache_semantics::BeforeRequest::Fresh(_) = cache_policy.before_request(request, now)
+    {
+        return true;
+    }
+    min_fresh.is_some_and(|min_fresh| cache_policy.age(now) < min_fresh)
+}
+
+/// the result of fetching a map tile from the server
+#[cfg(feature = "network")]
+#[derive(Debug)]
+enum MapTileFetchResult {
+    /// the map tile does not exist, contains the cache policy of the
+    /// negative response
+    Absent(http_cache_semantics::CachePolicy),
+    /// the server confirmed that the cached map tile is still valid,
+    /// contains the updated cache policy
+    NotModified(http_cache_semantics::CachePolicy),
+    /// the map tile was fetched, contains the raw data as received
+    /// from the server and the cache policy
+    Fetched(Vec<u8>, http_cache_semantics::CachePolicy),
+}
+
+/// the result of looking up a map tile in the local caches
+#[cfg(feature = "network")]
+#[derive(Debug)]
+enum MapTileCacheLookup<T> {
+    /// the cached answer is still fresh
+    Cached(T),
+    /// the map tile needs to be fetched from the server
+    Fetch(Box<MapTileFetch>),
+}
+
+/// a request for a map tile prepared by the `MapTileCache` which can be
+/// sent to the server without access to the `MapTileCache`
+///
+/// this allows the `SharedMapTileCache` to release its lock while waiting
+/// for the server
+#[cfg(feature = "network")]
+#[derive(derive_more::Debug)]
+struct MapTileFetch {
+    /// the map tile to fetch
+    map_tile_descriptor: MapTileDescriptor,
+    /// the client used to send the request
+    client: reqwest::Client,
+    /// the rate limiter for map tile requests to the server
+    #[debug(skip)]
+    ratelimiter: Option<std::sync::Arc<ratelimit::Ratelimiter>>,
+    /// the HTTP status codes the map tile server uses to indicate that
+    /// a map tile does not exist
+    absent_status_codes: Vec<reqwest::StatusCode>,
+    /// the URL of the map tile
+    url: String,
+    /// the unconditional request for the map tile
+    request: reqwest::Request,
+    /// a stale cached map tile and its cache policy, if this is set a
+    /// conditional request (using `If-None-Match`/`If-Modified-Since`)
+    /// is sent to revalidate it
+    revalidation_candidate: Option<(MapTile, http_cache_semantics::CachePolicy)>,
+}
+
+#[cfg(feature = "network")]
+impl MapTileFetch {
+    /// sends the request to the server and checks the response, this does
+    /// not touch any cache
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the HTTP request fails or the response is an
+    /// error or not an image
+    async fn send(&self) -> Result<MapTileFetchResult, MapTileCacheError> {
+        let mut conditional_request = self
+            .request
+            .try_clone()
+            .ok_or(MapTileCacheError::FailedToCloneRequest)?;
+        if let Some((_, stale_cache_policy)) = &self.revalidation_candidate {
+            if let http_cache_semantics::BeforeRequest::Stale {
+                request: revalidation_request,
+                matches: true,
+            } = stale_cache_policy.before_request(&self.request, std::time::SystemTime::now())
+            {
+                tracing::debug!(
+                    "Revalidating cached map tile with headers {:#?}",
+                    revalidation_request.headers
+                );
+                *conditional_request.headers_mut() = revalidation_request.headers;
+            }
+        }
+        tracing::debug!("Waiting for ratelimiter to fetch map tile from server");
+        if let Some(ratelimiter) = &self.ratelimiter {
+            if let Err(duration) = ratelimiter.try_wait() {
+                tracing::debug!("Ratelimit reached, delaying map tile request");
+                tokio::time::sleep(duration).await;
+                while let Err(duration) = ratelimiter.try_wait() {
+                    tokio::time::sleep(duration).await;
+                }
+            }
+        }
+        tracing::debug!("Fetching map tile from server at {}", self.url);
+        let response = self.client.execute(conditional_request).await?;
+        tracing::debug!(
+            "Server response received: status {}, headers\n{:#?}",
+            response.status(),
+            response.headers()
+        );
+        if let (reqwest::StatusCode::NOT_MODIFIED, Some((_, stale_cache_policy))) =
+            (response.status(), &self.revalidation_candidate)
+        {
+            tracing::debug!("Received 304 NOT MODIFIED response, cached map tile is still valid");
+            let (http_cache_semantics::AfterResponse::NotModified(cache_policy, _)
+            | http_cache_semantics::AfterResponse::Modified(cache_policy, _)) = stale_cache_policy
+                .after_response(&self.request, &response, std::time::SystemTime::now());
+            return Ok(MapTileFetchResult::NotModified(cache_policy));
+        }
+        if !response.status().is_success() {
+            if self.absent_status_codes.contains(&response.status()) {
+                // FORBIDDEN (403) is returned by the main map server when the
+                // file does not exist which likely means there is no
+                // region/map tile
+                tracing::debug!(
+                    "Received {} response, interpreting as no map tile for these grid coordinates",
+                    response.status()
+                );
+                return Ok(MapTileFetchResult::Absent(
+                    http_cache_semantics::CachePolicy::new(
+                        &self.request,
+                        &MapTileNegativeResponse(response),
+                    ),
+                ));
+            }
+            return Err(MapTileCacheError::HttpError(
+                self.url.to_owned(),
+                response.status(),
+                response.headers().to_owned(),
+                response.text().await?,
+            ));
+        }
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .map(|content_type| String::from_utf8_lossy(content_type.as_bytes()).into_owned());
+        if let Some(content_type) = &content_type {
+            if !content_type.to_lowercase().starts_with("image/") {
+                return Err(MapTileCacheError::UnexpectedContentType(
+                    content_type.to_owned(),
+                ));
+            }
+        }
+        let cache_policy = http_cache_semantics::CachePolicy::new(&self.request, &response);
+        let raw_map_tile = response.bytes().await?.to_vec();
+        if content_type.is_none() && image::guess_format(&raw_map_tile).is_err() {
+            return Err(MapTileCacheError::UnrecognizedImageData);
+        }
+        Ok(MapTileFetchResult::Fetched(raw_map_tile, cache_policy))
+    }
+}
+
+/// a wrapper around a response indicating an absent map tile (e.g. 403) to
+/// force its status to 404 so `http_cache_semantics::CachePolicy` becomes
+/// usable on those responses
+#[cfg(feature = "network")]
 #[derive(Debug)]
 pub struct MapTileNegativeResponse(reqwest::Response);
 
+#[cfg(feature = "network")]
 impl http_cache_semantics::ResponseLike for MapTileNegativeResponse {
     fn status(&self) -> http::status::StatusCode {
-        match self.0.status() {
-            http::status::StatusCode::FORBIDDEN => http::status::StatusCode::NOT_FOUND,
-            status => status,
-        }
+        http::status::StatusCode::NOT_FOUND
     }
 
     fn headers(&self) -> &http::header::HeaderMap {
@@ -348,39 +1164,95 @@ impl http_cache_semantics::ResponseLike for MapTileNegativeResponse {
     }
 }
 
+#[cfg(feature = "network")]
 impl MapTileCache {
     /// creates a new `MapTileCache`
-    #[allow(clippy::missing_panics_doc)]
     #[must_use]
     pub fn new(cache_directory: PathBuf, ratelimiter: Option<ratelimit::Ratelimiter>) -> Self {
+        Self::with_optional_cache_directory(Some(cache_directory), ratelimiter)
+    }
+
+    /// creates a new `MapTileCache` that does not use a cache directory
+    /// and only caches map tiles in memory
+    #[must_use]
+    pub fn in_memory(ratelimiter: Option<ratelimit::Ratelimiter>) -> Self {
+        Self::with_optional_cache_directory(None, ratelimiter)
+    }
+
+    /// creates a `MapTileCacheBuilder` to configure a new `MapTileCache`
+    #[must_use]
+    pub fn builder() -> MapTileCacheBuilder {
+        MapTileCacheBuilder::default()
+    }
+
+    /// creates a new `MapTileCache` with or without a cache directory
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    fn with_optional_cache_directory(
+        cache_directory: Option<PathBuf>,
+        ratelimiter: Option<ratelimit::Ratelimiter>,
+    ) -> Self {
         // unwrap is okay here because we know that the literal 16 is non-zero
         // same reason for missing_panics_doc above
         #[allow(clippy::unwrap_used)]
         let cache = lru::LruCache::new(std::num::NonZeroUsize::new(16).unwrap());
         MapTileCache {
             client: reqwest::Client::new(),
-            ratelimiter,
+            ratelimiter: ratelimiter.map(std::sync::Arc::new),
+            grid_profile: GridProfile::second_life_main(),
+            absent_status_codes: DEFAULT_ABSENT_STATUS_CODES.to_vec(),
+            min_fresh: None,
             cache_directory,
             cache,
         }
     }
 
-    /// the file name of a map tile cache file
+    /// sets the `GridProfile` of the grid to fetch map tiles from,
+    /// e.g. for other grids or for mirrors or proxies of the Second Life
+    /// main map server
     #[must_use]
-    fn map_tile_file_name(&self, map_tile_descriptor: &MapTileDescriptor) -> String {
-        format!(
-            "map-{}-{}-{}-objects.jpg",
-            map_tile_descriptor.zoom_level(),
-            map_tile_descriptor.lower_left_corner().x(),
-            map_tile_descriptor.lower_left_corner().y(),
-        )
+    pub fn with_grid_profile(mut self, grid_profile: GridProfile) -> Self {
+        self.grid_profile = grid_profile;
+        self
+    }
+
+    /// sets the HTTP status codes that indicate that a map tile does not
+    /// exist, e.g. some mirrors or proxies return 404 NOT FOUND instead of
+    /// the 403 FORBIDDEN returned by the Second Life main map server
+    #[must_use]
+    pub fn with_absent_status_codes(
+        mut self,
+        absent_status_codes: impl IntoIterator<Item = reqwest::StatusCode>,
+    ) -> Self {
+        self.absent_status_codes = absent_status_codes.into_iter().collect();
+        self
+    }
+
+    /// sets a minimum duration for which cached map tiles (and absences) are
+    /// considered fresh even if the cache policy sent by the server says
+    /// otherwise
+    ///
+    /// map tiles only change infrequently so this can cut down on the
+    /// number of revalidation requests if slightly outdated maps are
+    /// acceptable
+    #[must_use]
+    pub fn with_min_fresh(mut self, min_fresh: std::time::Duration) -> Self {
+        self.min_fresh = Some(min_fresh);
+        self
+    }
+
+    /// the file name of a map tile cache file
+    #[must_use]
+    fn map_tile_file_name(&self, map_tile_descriptor: &MapTileDescriptor) -> String {
+        map_tile_descriptor.tile_filename()
     }
 
     /// the file name of a map tile in the cache directory
     #[must_use]
-    fn map_tile_cache_file_name(&self, map_tile_descriptor: &MapTileDescriptor) -> PathBuf {
-        self.cache_directory
-            .join(self.map_tile_file_name(map_tile_descriptor))
+    fn map_tile_cache_file_name(&self, map_tile_descriptor: &MapTileDescriptor) -> Option<PathBuf> {
+        self.cache_directory.as_ref().map(|cache_directory| {
+            cache_directory.join(self.map_tile_file_name(map_tile_descriptor))
+        })
     }
 
     /// the file name marking a negative response in the cache directory
@@ -388,29 +1260,30 @@ impl MapTileCache {
     fn map_tile_cache_negative_response_file_name(
         &self,
         map_tile_descriptor: &MapTileDescriptor,
-    ) -> PathBuf {
-        self.cache_directory.join(format!(
-            "{}.does-not-exist",
-            self.map_tile_file_name(map_tile_descriptor)
-        ))
+    ) -> Option<PathBuf> {
+        self.cache_directory.as_ref().map(|cache_directory| {
+            cache_directory.join(format!(
+                "{}.does-not-exist",
+                self.map_tile_file_name(map_tile_descriptor)
+            ))
+        })
     }
 
     /// the file name of the cache policy file in the cache directory
     #[must_use]
-    fn cache_policy_file_name(&self, map_tile_descriptor: &MapTileDescriptor) -> PathBuf {
-        self.cache_directory.join(format!(
-            "{}.cache-policy.json",
-            self.map_tile_file_name(map_tile_descriptor)
-        ))
+    fn cache_policy_file_name(&self, map_tile_descriptor: &MapTileDescriptor) -> Option<PathBuf> {
+        self.cache_directory.as_ref().map(|cache_directory| {
+            cache_directory.join(format!(
+                "{}.cache-policy.json",
+                self.map_tile_file_name(map_tile_descriptor)
+            ))
+        })
     }
 
-    /// the URL of a map tile on the Second Life main map server
+    /// the URL of a map tile on the map server
     #[must_use]
     fn map_tile_url(&self, map_tile_descriptor: &MapTileDescriptor) -> String {
-        format!(
-            "https://secondlife-maps-cdn.akamaized.net/{}",
-            self.map_tile_file_name(map_tile_descriptor),
-        )
+        map_tile_url(self.grid_profile.map_tile_base_url(), map_tile_descriptor)
     }
 
     /// check if a cache entry is missing, invalid or valid (either cache policy + map tile or cache policy + negative response)
@@ -419,10 +1292,12 @@ impl MapTileCache {
         map_tile_descriptor: &MapTileDescriptor,
     ) -> Result<MapTileCacheEntryStatus, MapTileCacheError> {
         match (
-            self.cache_policy_file_name(map_tile_descriptor).exists(),
-            self.map_tile_cache_file_name(map_tile_descriptor).exists(),
+            self.cache_policy_file_name(map_tile_descriptor)
+                .is_some_and(|file_name| file_name.exists()),
+            self.map_tile_cache_file_name(map_tile_descriptor)
+                .is_some_and(|file_name| file_name.exists()),
             self.map_tile_cache_negative_response_file_name(map_tile_descriptor)
-                .exists(),
+                .is_some_and(|file_name| file_name.exists()),
         ) {
             (false, false, false) => Ok(MapTileCacheEntryStatus::Missing),
             (true, true, false) => Ok(MapTileCacheEntryStatus::Valid),
@@ -463,7 +1338,7 @@ impl MapTileCache {
         let Some(cache_policy) = self.load_cache_policy(map_tile_descriptor).await? else {
             return Err(MapTileCacheError::CachePolicyError);
         };
-        if cache_file.exists() {
+        if let Some(cache_file) = cache_file.filter(|cache_file| cache_file.exists()) {
             let cached_map_tile = image::ImageReader::open(cache_file)
                 .map_err(MapTileCacheError::CacheDirectoryFileError)?
                 .decode()?;
@@ -487,20 +1362,66 @@ impl MapTileCache {
     ) -> Result<(), MapTileCacheError> {
         tracing::debug!("Removing {map_tile_descriptor:?} from map tile cache");
         self.cache.pop(map_tile_descriptor);
-        let cache_file = self.map_tile_cache_file_name(map_tile_descriptor);
-        let cache_file_negative_response =
-            self.map_tile_cache_negative_response_file_name(map_tile_descriptor);
-        let cache_policy_file = self.cache_policy_file_name(map_tile_descriptor);
-        if cache_file.exists() {
-            std::fs::remove_file(cache_file).map_err(MapTileCacheError::CacheDirectoryFileError)?;
-        }
-        if cache_file_negative_response.exists() {
-            std::fs::remove_file(cache_file_negative_response)
-                .map_err(MapTileCacheError::CacheDirectoryFileError)?;
+        for file_name in [
+            self.map_tile_cache_file_name(map_tile_descriptor),
+            self.map_tile_cache_negative_response_file_name(map_tile_descriptor),
+            self.cache_policy_file_name(map_tile_descriptor),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if file_name.exists() {
+                std::fs::remove_file(file_name)
+                    .map_err(MapTileCacheError::CacheDirectoryFileError)?;
+            }
         }
-        if cache_policy_file.exists() {
-            std::fs::remove_file(cache_policy_file)
-                .map_err(MapTileCacheError::CacheDirectoryFileError)?;
+        Ok(())
+    }
+
+    /// removes all map tiles covering the region at the given grid
+    /// coordinates on any zoom level from the in-memory and disk caches
+    ///
+    /// this can be used to force a refresh of the map tiles for a region
+    /// that changed recently
+    ///
+    /// # Errors
+    ///
+    /// returns an error if removing the files from the disk cache fails
+    pub async fn invalidate_region(
+        &mut self,
+        grid_coordinates: &GridCoordinates,
+    ) -> Result<(), MapTileCacheError> {
+        self.invalidate_rectangle(&GridRectangle::new(
+            grid_coordinates.to_owned(),
+            grid_coordinates.to_owned(),
+        ))
+        .await
+    }
+
+    /// removes all map tiles covering any of the regions in the given grid
+    /// rectangle on any zoom level from the in-memory and disk caches
+    ///
+    /// # Errors
+    ///
+    /// returns an error if removing the files from the disk cache fails
+    pub async fn invalidate_rectangle(
+        &mut self,
+        grid_rectangle: &GridRectangle,
+    ) -> Result<(), MapTileCacheError> {
+        for zoom_level in 1..=8 {
+            let zoom_level = ZoomLevel::try_new(zoom_level)?;
+            let mut map_tile_descriptors = std::collections::HashSet::new();
+            for x in grid_rectangle.x_range() {
+                for y in grid_rectangle.y_range() {
+                    map_tile_descriptors.insert(MapTileDescriptor::new(
+                        zoom_level,
+                        GridCoordinates::new(x, y),
+                    ));
+                }
+            }
+            for map_tile_descriptor in map_tile_descriptors {
+                self.remove_cached_tile(&map_tile_descriptor).await?;
+            }
         }
         Ok(())
     }
@@ -515,10 +1436,12 @@ impl MapTileCache {
         &self,
         map_tile_descriptor: &MapTileDescriptor,
     ) -> Result<Option<http_cache_semantics::CachePolicy>, MapTileCacheError> {
-        let cache_policy_file = self.cache_policy_file_name(map_tile_descriptor);
-        if !cache_policy_file.exists() {
+        let Some(cache_policy_file) = self
+            .cache_policy_file_name(map_tile_descriptor)
+            .filter(|cache_policy_file| cache_policy_file.exists())
+        else {
             return Ok(None);
-        }
+        };
         let cache_policy = std::fs::read_to_string(cache_policy_file)
             .map_err(MapTileCacheError::CacheDirectoryFileError)?;
         Ok(serde_json::from_str(&cache_policy)?)
@@ -535,16 +1458,19 @@ impl MapTileCache {
         map_tile_descriptor: &MapTileDescriptor,
         cache_policy: http_cache_semantics::CachePolicy,
     ) -> Result<(), MapTileCacheError> {
-        if !self.cache_directory.exists() {
-            std::fs::create_dir_all(&self.cache_directory)
+        let (Some(cache_directory), Some(cache_policy_file)) = (
+            &self.cache_directory,
+            self.cache_policy_file_name(map_tile_descriptor),
+        ) else {
+            return Ok(());
+        };
+        if !cache_directory.exists() {
+            std::fs::create_dir_all(cache_directory)
                 .map_err(MapTileCacheError::CacheDirectoryFileError)?;
         }
         let cache_policy = serde_json::to_string(&cache_policy)?;
-        std::fs::write(
-            self.cache_policy_file_name(map_tile_descriptor),
-            cache_policy,
-        )
-        .map_err(MapTileCacheError::CacheDirectoryFileError)?;
+        std::fs::write(cache_policy_file, cache_policy)
+            .map_err(MapTileCacheError::CacheDirectoryFileError)?;
         Ok(())
     }
 
@@ -564,10 +1490,12 @@ impl MapTileCache {
             tracing::debug!("Caching absence of map tile {map_tile_descriptor:?}");
             self.store_cache_policy(map_tile_descriptor, cache_policy.to_owned())
                 .await?;
-            let cache_file_negative_response =
-                self.map_tile_cache_negative_response_file_name(map_tile_descriptor);
-            std::fs::File::create(cache_file_negative_response)
-                .map_err(MapTileCacheError::CacheDirectoryFileError)?;
+            if let Some(cache_file_negative_response) =
+                self.map_tile_cache_negative_response_file_name(map_tile_descriptor)
+            {
+                std::fs::File::create(cache_file_negative_response)
+                    .map_err(MapTileCacheError::CacheDirectoryFileError)?;
+            }
             self.cache
                 .put(map_tile_descriptor.clone(), (None, cache_policy));
         } else {
@@ -576,8 +1504,11 @@ impl MapTileCache {
         Ok(())
     }
 
-    /// stores a tile in the cache if the cache policy indicates that
-    /// it is storable
+    /// stores the raw map tile as received from the server in the disk cache
+    /// if the cache policy indicates that it is storable
+    ///
+    /// the raw data is stored unmodified to avoid losing quality by decoding
+    /// and re-encoding the JPEG image
     ///
     /// # Errors
     ///
@@ -586,20 +1517,17 @@ impl MapTileCache {
     async fn cache_tile(
         &mut self,
         map_tile_descriptor: &MapTileDescriptor,
-        map_tile: &MapTile,
+        raw_map_tile: &[u8],
         cache_policy: http_cache_semantics::CachePolicy,
     ) -> Result<(), MapTileCacheError> {
         if cache_policy.is_storable() {
             tracing::debug!("Caching map tile {map_tile_descriptor:?}");
-            self.store_cache_policy(map_tile_descriptor, cache_policy.to_owned())
+            self.store_cache_policy(map_tile_descriptor, cache_policy)
                 .await?;
-            map_tile
-                .image
-                .save(self.map_tile_cache_file_name(map_tile_descriptor))?;
-            self.cache.put(
-                map_tile_descriptor.clone(),
-                (Some(map_tile.to_owned()), cache_policy),
-            );
+            if let Some(cache_file) = self.map_tile_cache_file_name(map_tile_descriptor) {
+                std::fs::write(cache_file, raw_map_tile)
+                    .map_err(MapTileCacheError::CacheDirectoryFileError)?;
+            }
         } else {
             tracing::warn!(
                 "Map tile {map_tile_descriptor:?} not storable according to cache policy"
@@ -619,10 +1547,30 @@ impl MapTileCache {
         &mut self,
         map_tile_descriptor: &MapTileDescriptor,
     ) -> Result<Option<MapTile>, MapTileCacheError> {
+        let map_tile_fetch = match self.lookup_map_tile(map_tile_descriptor).await? {
+            MapTileCacheLookup::Cached(cached_map_tile) => return Ok(cached_map_tile),
+            MapTileCacheLookup::Fetch(map_tile_fetch) => *map_tile_fetch,
+        };
+        let fetch_result = map_tile_fetch.send().await?;
+        self.finish_get_map_tile(map_tile_fetch, fetch_result).await
+    }
+
+    /// looks up a map tile in the local caches for `get_map_tile`, if
+    /// there is no fresh cached map tile or absence the request to fetch
+    /// it from the server is prepared
+    ///
+    /// # Errors
+    ///
+    /// returns an error if loading the map tile from the cache fails
+    async fn lookup_map_tile(
+        &mut self,
+        map_tile_descriptor: &MapTileDescriptor,
+    ) -> Result<MapTileCacheLookup<Option<MapTile>>, MapTileCacheError> {
         tracing::debug!("Map tile {map_tile_descriptor:?} requested");
         let url = self.map_tile_url(map_tile_descriptor);
         let request = self.client.get(&url).build()?;
         let now = std::time::SystemTime::now();
+        let mut revalidation_candidate = None;
         if let Some((cached_map_tile, cache_policy)) =
             self.fetch_cached_map_tile(map_tile_descriptor).await?
         {
@@ -631,63 +1579,66 @@ impl MapTileCache {
             } else {
                 tracing::debug!("Found matching map tile absence in cache, checking freshness");
             }
-            if let http_cache_semantics::BeforeRequest::Fresh(_) =
-                cache_policy.before_request(&request, now)
-            {
+            if is_fresh(&cache_policy, &request, now, self.min_fresh) {
                 if cached_map_tile.is_some() {
                     tracing::debug!("Using cached map tile");
                 } else {
                     tracing::debug!("Using cached map tile absence");
                 }
-                return Ok(cached_map_tile);
+                return Ok(MapTileCacheLookup::Cached(cached_map_tile));
             }
-            tracing::debug!("Map tile cache not fresh, removing from cache");
-            self.remove_cached_tile(map_tile_descriptor).await?;
-        }
-        tracing::debug!("Waiting for ratelimiter to fetch map tile from server");
-        if let Some(ratelimiter) = &self.ratelimiter {
-            while let Err(duration) = ratelimiter.try_wait() {
-                tokio::time::sleep(duration).await;
+            if let Some(cached_map_tile) = cached_map_tile {
+                tracing::debug!("Map tile cache not fresh, revalidating");
+                revalidation_candidate = Some((cached_map_tile, cache_policy));
+            } else {
+                tracing::debug!("Map tile cache not fresh, removing from cache");
+                self.remove_cached_tile(map_tile_descriptor).await?;
             }
         }
-        tracing::debug!("Fetching map tile from server at {}", url);
-        let response = self
-            .client
-            .execute(
-                request
-                    .try_clone()
-                    .ok_or(MapTileCacheError::FailedToCloneRequest)?,
-            )
+        Ok(MapTileCacheLookup::Fetch(self.prepare_fetch(
+            map_tile_descriptor,
+            revalidation_candidate,
+        )?))
+    }
+
+    /// stores the result of fetching a map tile for `get_map_tile` in the
+    /// cache and decodes the map tile
+    ///
+    /// # Errors
+    ///
+    /// returns an error if storing the result in the cache fails or the
+    /// map tile fails to be parsed as an image
+    async fn finish_get_map_tile(
+        &mut self,
+        map_tile_fetch: MapTileFetch,
+        fetch_result: MapTileFetchResult,
+    ) -> Result<Option<MapTile>, MapTileCacheError> {
+        let map_tile_descriptor = &map_tile_fetch.map_tile_descriptor;
+        self.store_fetch_result(map_tile_descriptor, &fetch_result)
             .await?;
-        tracing::debug!(
-            "Server response received: status {}, headers\n{:#?}",
-            response.status(),
-            response.headers()
-        );
-        if !response.status().is_success() {
-            if response.status() == reqwest::StatusCode::FORBIDDEN {
-                // FORBIDDEN (403) is returned when the file does not exist
-                // which likely means there is no region/map tile
-                tracing::debug!("Received 403 FORBIDDEN response, interpreting as no map tile for these grid coordinates");
-                let cache_policy = http_cache_semantics::CachePolicy::new(
-                    &request,
-                    &MapTileNegativeResponse(response),
-                );
-                self.cache_missing_tile(map_tile_descriptor, cache_policy)
-                    .await?;
-                return Ok(None);
+        let (raw_map_tile, cache_policy) = match fetch_result {
+            MapTileFetchResult::Absent(_) => return Ok(None),
+            MapTileFetchResult::NotModified(cache_policy) => {
+                // we only receive this if we sent a conditional request for
+                // our revalidation candidate
+                let cached_map_tile = map_tile_fetch
+                    .revalidation_candidate
+                    .map(|(cached_map_tile, _)| cached_map_tile);
+                if let Some(cached_map_tile) = &cached_map_tile {
+                    tracing::debug!("Using revalidated cached map tile");
+                    if cache_policy.is_storable() {
+                        self.cache.put(
+                            map_tile_descriptor.clone(),
+                            (Some(cached_map_tile.to_owned()), cache_policy),
+                        );
+                    }
+                }
+                return Ok(cached_map_tile);
             }
-            return Err(MapTileCacheError::HttpError(
-                url.to_owned(),
-                response.status(),
-                response.headers().to_owned(),
-                response.text().await?,
-            ));
-        }
-        let cache_policy = http_cache_semantics::CachePolicy::new(&request, &response);
-        let raw_response_body = response.bytes().await?;
+            MapTileFetchResult::Fetched(raw_map_tile, cache_policy) => (raw_map_tile, cache_policy),
+        };
         tracing::debug!("Parsing received map tile to image");
-        let image = image::ImageReader::new(std::io::Cursor::new(raw_response_body))
+        let image = image::ImageReader::new(std::io::Cursor::new(raw_map_tile))
             .with_guessed_format()
             .map_err(MapTileCacheError::ImageFormatGuessError)?
             .decode()?;
@@ -695,15 +1646,80 @@ impl MapTileCache {
             descriptor: map_tile_descriptor.to_owned(),
             image,
         };
-        self.cache_tile(map_tile_descriptor, &map_tile, cache_policy)
-            .await?;
+        if cache_policy.is_storable() {
+            self.cache.put(
+                map_tile_descriptor.clone(),
+                (Some(map_tile.to_owned()), cache_policy),
+            );
+        }
         tracing::debug!("Returning freshly fetched map tile");
         Ok(Some(map_tile))
     }
 
+    /// prepares a request to fetch a map tile from the server, optionally
+    /// revalidating a stale cached map tile
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the request could not be built
+    fn prepare_fetch(
+        &self,
+        map_tile_descriptor: &MapTileDescriptor,
+        revalidation_candidate: Option<(MapTile, http_cache_semantics::CachePolicy)>,
+    ) -> Result<Box<MapTileFetch>, MapTileCacheError> {
+        let url = self.map_tile_url(map_tile_descriptor);
+        let request = self.client.get(&url).build()?;
+        Ok(Box::new(MapTileFetch {
+            map_tile_descriptor: map_tile_descriptor.to_owned(),
+            client: self.client.clone(),
+            ratelimiter: self.ratelimiter.clone(),
+            absent_status_codes: self.absent_status_codes.clone(),
+            url,
+            request,
+            revalidation_candidate,
+        }))
+    }
+
+    /// stores the raw map tile (or its absence) fetched from the server in
+    /// the disk cache replacing any previously cached data, for a
+    /// revalidated map tile only the cache policy is updated
+    ///
+    /// # Errors
+    ///
+    /// returns an error if storing the result in the cache fails
+    async fn store_fetch_result(
+        &mut self,
+        map_tile_descriptor: &MapTileDescriptor,
+        fetch_result: &MapTileFetchResult,
+    ) -> Result<(), MapTileCacheError> {
+        match fetch_result {
+            MapTileFetchResult::NotModified(cache_policy) => {
+                if cache_policy.is_storable() {
+                    self.store_cache_policy(map_tile_descriptor, cache_policy.to_owned())
+                        .await?;
+                }
+            }
+            MapTileFetchResult::Absent(cache_policy) => {
+                self.remove_cached_tile(map_tile_descriptor).await?;
+                self.cache_missing_tile(map_tile_descriptor, cache_policy.to_owned())
+                    .await?;
+            }
+            MapTileFetchResult::Fetched(raw_map_tile, cache_policy) => {
+                self.remove_cached_tile(map_tile_descriptor).await?;
+                self.cache_tile(map_tile_descriptor, raw_map_tile, cache_policy.to_owned())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     /// figures out if a map tile exist by checking the local in-memory and
     /// disk caches or fetching the map tile from the server
     ///
+    /// the requests built for the cache freshness checks are never sent,
+    /// the only network access happens in `MapTileFetch::send` which waits
+    /// for the ratelimiter
+    ///
     /// # Errors
     ///
     /// returns an error if fetching the map tile from cache or remotely fails
@@ -711,39 +1727,77 @@ impl MapTileCache {
         &mut self,
         map_tile_descriptor: &MapTileDescriptor,
     ) -> Result<bool, MapTileCacheError> {
+        let map_tile_fetch = match self.lookup_map_tile_existence(map_tile_descriptor).await? {
+            MapTileCacheLookup::Cached(exists) => return Ok(exists),
+            MapTileCacheLookup::Fetch(map_tile_fetch) => *map_tile_fetch,
+        };
+        let fetch_result = map_tile_fetch.send().await?;
+        self.finish_does_map_tile_exist(&map_tile_fetch, &fetch_result)
+            .await
+    }
+
+    /// stores the result of fetching a map tile for `does_map_tile_exist`
+    /// in the cache
+    ///
+    /// # Errors
+    ///
+    /// returns an error if storing the result in the cache fails
+    async fn finish_does_map_tile_exist(
+        &mut self,
+        map_tile_fetch: &MapTileFetch,
+        fetch_result: &MapTileFetchResult,
+    ) -> Result<bool, MapTileCacheError> {
+        self.store_fetch_result(&map_tile_fetch.map_tile_descriptor, fetch_result)
+            .await?;
+        Ok(!matches!(fetch_result, MapTileFetchResult::Absent(_)))
+    }
+
+    /// looks up the existence of a map tile in the local caches for
+    /// `does_map_tile_exist`, if the cache is not fresh the request to
+    /// fetch the map tile from the server is prepared
+    ///
+    /// # Errors
+    ///
+    /// returns an error if loading the cache policy fails
+    async fn lookup_map_tile_existence(
+        &mut self,
+        map_tile_descriptor: &MapTileDescriptor,
+    ) -> Result<MapTileCacheLookup<bool>, MapTileCacheError> {
         let url = self.map_tile_url(map_tile_descriptor);
         if let Some((map_tile, cache_policy)) = self.cache.get(map_tile_descriptor) {
             let request = self.client.get(&url).build()?;
             let now = std::time::SystemTime::now();
-            if let http_cache_semantics::BeforeRequest::Fresh(_) =
-                cache_policy.before_request(&request, now)
-            {
-                return Ok(map_tile.is_some());
+            if is_fresh(cache_policy, &request, now, self.min_fresh) {
+                return Ok(MapTileCacheLookup::Cached(map_tile.is_some()));
             }
         }
         if self.cache_entry_status(map_tile_descriptor).await? == MapTileCacheEntryStatus::Valid {
             if let Some(cache_policy) = self.load_cache_policy(map_tile_descriptor).await? {
                 let request = self.client.get(&url).build()?;
                 let now = std::time::SystemTime::now();
-                if let http_cache_semantics::BeforeRequest::Fresh(_) =
-                    cache_policy.before_request(&request, now)
-                {
-                    if self
-                        .map_tile_cache_negative_response_file_name(map_tile_descriptor)
-                        .exists()
-                    {
-                        return Ok(false);
-                    }
-                    return Ok(true);
+                if is_fresh(&cache_policy, &request, now, self.min_fresh) {
+                    return Ok(MapTileCacheLookup::Cached(
+                        !self
+                            .map_tile_cache_negative_response_file_name(map_tile_descriptor)
+                            .is_some_and(|file_name| file_name.exists()),
+                    ));
                 }
             }
         }
-        Ok(self.get_map_tile(map_tile_descriptor).await?.is_some())
+        // no need to decode the map tile just to check for its existence
+        // so there is no revalidation candidate
+        Ok(MapTileCacheLookup::Fetch(
+            self.prepare_fetch(map_tile_descriptor, None)?,
+        ))
     }
 
     /// figures out if a region exists based on the existence of map tiles for it, starting with the lowest zoom level
     /// and potentially going up to the highest one if all the other zoom levels have a tile for that region
     ///
+    /// grid coordinates outside of the area with regions of the grid (see
+    /// `GridProfile::is_plausible_region`) are considered to not have a
+    /// region without any requests
+    ///
     /// # Errors
     ///
     /// returns an error if fetching map tiles from cache or remotely fails
@@ -751,6 +1805,12 @@ impl MapTileCache {
         &mut self,
         grid_coordinates: &GridCoordinates,
     ) -> Result<bool, MapTileCacheError> {
+        if !self.grid_profile.is_plausible_region(grid_coordinates) {
+            tracing::debug!(
+                "Region {grid_coordinates:?} is outside of the grid, region does not exist"
+            );
+            return Ok(false);
+        }
         for zoom_level in (1..=8).rev() {
             tracing::debug!("Checking if zoom level {zoom_level} map tile exists for region {grid_coordinates:?}");
             let map_tile_descriptor = MapTileDescriptor::new(
@@ -761,8 +1821,6 @@ impl MapTileCache {
                 tracing::debug!("No map tile found, region {grid_coordinates:?} does not exist");
                 return Ok(false);
             }
-            let cache_entry_status = self.cache_entry_status(&map_tile_descriptor).await?;
-            if cache_entry_status == MapTileCacheEntryStatus::Valid {}
         }
         tracing::debug!(
             "Map tiles exist for {grid_coordinates:?} on all zoom levels, region exists"
@@ -771,6 +1829,226 @@ impl MapTileCache {
     }
 }
 
+/// a cloneable handle to a `MapTileCache` that can be shared between
+/// concurrent tasks
+///
+/// all clones refer to the same underlying cache, access to it is
+/// serialized via a mutex but the mutex is not held while waiting for the
+/// map tile server so concurrent tasks can fetch different map tiles at the
+/// same time
+///
+/// concurrent requests for the same uncached map tile may both fetch it from
+/// the server, the last response to arrive is the one that stays in the cache
+#[cfg(feature = "network")]
+#[derive(Debug, Clone)]
+pub struct SharedMapTileCache(std::sync::Arc<tokio::sync::Mutex<MapTileCache>>);
+
+#[cfg(feature = "network")]
+impl SharedMapTileCache {
+    /// creates a new `SharedMapTileCache` wrapping the given `MapTileCache`
+    #[must_use]
+    pub fn new(map_tile_cache: MapTileCache) -> Self {
+        Self(std::sync::Arc::new(tokio::sync::Mutex::new(map_tile_cache)))
+    }
+
+    /// fetches a map tile from the shared cache or the server
+    ///
+    /// see `MapTileCache::get_map_tile`
+    ///
+    /// # Errors
+    ///
+    /// returns an error if fetching the map tile from cache or remotely fails
+    pub async fn get_map_tile(
+        &self,
+        map_tile_descriptor: &MapTileDescriptor,
+    ) -> Result<Option<MapTile>, MapTileCacheError> {
+        let map_tile_fetch = match self
+            .0
+            .lock()
+            .await
+            .lookup_map_tile(map_tile_descriptor)
+            .await?
+        {
+            MapTileCacheLookup::Cached(cached_map_tile) => return Ok(cached_map_tile),
+            MapTileCacheLookup::Fetch(map_tile_fetch) => *map_tile_fetch,
+        };
+        let fetch_result = map_tile_fetch.send().await?;
+        self.0
+            .lock()
+            .await
+            .finish_get_map_tile(map_tile_fetch, fetch_result)
+            .await
+    }
+
+    /// figures out if a map tile exists
+    ///
+    /// see `MapTileCache::does_map_tile_exist`
+    ///
+    /// # Errors
+    ///
+    /// returns an error if fetching the map tile from cache or remotely fails
+    pub async fn does_map_tile_exist(
+        &self,
+        map_tile_descriptor: &MapTileDescriptor,
+    ) -> Result<bool, MapTileCacheError> {
+        let map_tile_fetch = match self
+            .0
+            .lock()
+            .await
+            .lookup_map_tile_existence(map_tile_descriptor)
+            .await?
+        {
+            MapTileCacheLookup::Cached(exists) => return Ok(exists),
+            MapTileCacheLookup::Fetch(map_tile_fetch) => *map_tile_fetch,
+        };
+        let fetch_result = map_tile_fetch.send().await?;
+        self.0
+            .lock()
+            .await
+            .finish_does_map_tile_exist(&map_tile_fetch, &fetch_result)
+            .await
+    }
+
+    /// figures out if a region exists
+    ///
+    /// see `MapTileCache::does_region_exist`
+    ///
+    /// # Errors
+    ///
+    /// returns an error if fetching map tiles from cache or remotely fails
+    pub async fn does_region_exist(
+        &self,
+        grid_coordinates: &GridCoordinates,
+    ) -> Result<bool, MapTileCacheError> {
+        if !self
+            .0
+            .lock()
+            .await
+            .grid_profile
+            .is_plausible_region(grid_coordinates)
+        {
+            return Ok(false);
+        }
+        for zoom_level in (1..=8).rev() {
+            let map_tile_descriptor = MapTileDescriptor::new(
+                ZoomLevel::try_new(zoom_level)?,
+                grid_coordinates.to_owned(),
+            );
+            if !self.does_map_tile_exist(&map_tile_descriptor).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// locks the underlying `MapTileCache` for uses that need mutable
+    /// access, e.g. `Map::new`
+    pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, MapTileCache> {
+        self.0.lock().await
+    }
+}
+
+#[cfg(feature = "network")]
+impl From<MapTileCache> for SharedMapTileCache {
+    fn from(map_tile_cache: MapTileCache) -> Self {
+        Self::new(map_tile_cache)
+    }
+}
+
+/// resolves the region names of the waypoints of a `USBNotecard` to
+/// `GridCoordinates`
+///
+/// # Errors
+///
+/// fails if the region name to grid coordinate conversion fails or returns
+/// no grid coordinates for one of the regions
+#[cfg(feature = "network")]
+async fn route_grid_coordinates<B: RegionLookupBackend>(
+    region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+    usb_notecard: &USBNotecard,
+) -> Result<Vec<(USBWaypoint, GridCoordinates)>, MapError> {
+    let mut result = Vec::new();
+    for waypoint in usb_notecard.waypoints() {
+        let Some(grid_coordinates) = region_name_to_grid_coordinates_cache
+            .get_grid_coordinates(waypoint.location().region_name())
+            .await?
+        else {
+            return Err(MapError::NoGridCoordinatesForRegion(
+                waypoint.location().region_name().to_owned(),
+            ));
+        };
+        result.push((waypoint.to_owned(), grid_coordinates));
+    }
+    Ok(result)
+}
+
+/// the color at the given position (0.0 to 1.0) of a gradient defined by
+/// evenly spaced color stops
+///
+/// the gradient must not be empty
+#[must_use]
+fn sample_gradient(gradient: &[image::Rgba<u8>], position: f32) -> image::Rgba<u8> {
+    let scaled_position = position.clamp(0f32, 1f32) * (gradient.len() - 1) as f32;
+    let lower_index = scaled_position.floor() as usize;
+    let upper_index = (lower_index + 1).min(gradient.len() - 1);
+    let fraction = scaled_position - lower_index as f32;
+    let (lower, upper) = (gradient[lower_index], gradient[upper_index]);
+    image::Rgba([
+        blend_channel(lower.0[0], upper.0[0], fraction),
+        blend_channel(lower.0[1], upper.0[1], fraction),
+        blend_channel(lower.0[2], upper.0[2], fraction),
+        blend_channel(lower.0[3], upper.0[3], fraction),
+    ])
+}
+
+/// linearly interpolates between two color channel values, `fraction`
+/// 0.0 returns `from` and 1.0 returns `to`
+#[must_use]
+fn blend_channel(from: u8, to: u8, fraction: f32) -> u8 {
+    (f32::from(from) * (1f32 - fraction) + f32::from(to) * fraction).round() as u8
+}
+
+/// the maximum difference per color channel for two colors to be
+/// considered the same despite JPEG compression artifacts
+#[cfg(feature = "network")]
+const COLOR_MATCH_TOLERANCE: u8 = 24;
+
+/// the most common color in an image, colors are grouped by their upper
+/// four bits per channel to be robust against JPEG compression artifacts
+///
+/// returns None for an empty image
+#[cfg(feature = "network")]
+#[must_use]
+fn dominant_color<I>(image: &I) -> Option<image::Rgba<u8>>
+where
+    I: image::GenericImageView<Pixel = image::Rgba<u8>>,
+{
+    let mut counts = std::collections::HashMap::new();
+    for (_x, _y, pixel) in image.pixels() {
+        *counts
+            .entry([pixel.0[0] >> 4, pixel.0[1] >> 4, pixel.0[2] >> 4])
+            .or_insert(0usize) += 1;
+    }
+    let (bucket, _count) = counts.into_iter().max_by_key(|(_bucket, count)| *count)?;
+    Some(image::Rgba([
+        (bucket[0] << 4) | 8,
+        (bucket[1] << 4) | 8,
+        (bucket[2] << 4) | 8,
+        255,
+    ]))
+}
+
+/// whether two colors are the same apart from JPEG compression artifacts,
+/// the alpha channel is ignored
+#[cfg(feature = "network")]
+#[must_use]
+fn colors_match(a: image::Rgba<u8>, b: image::Rgba<u8>) -> bool {
+    a.0[..3]
+        .iter()
+        .zip(b.0[..3].iter())
+        .all(|(a, b)| a.abs_diff(*b) <= COLOR_MATCH_TOLERANCE)
+}
+
 /// represents a map assembled from map tiles
 #[derive(Debug, Clone)]
 pub struct Map {
@@ -786,6 +2064,7 @@ pub struct Map {
 #[derive(Debug, thiserror::Error)]
 pub enum MapError {
     /// an error in the map tile cache
+    #[cfg(feature = "network")]
     #[error("error in map tile cache while assembling map: {0}")]
     MapTileCacheError(#[from] MapTileCacheError),
     /// an error occurred when trying to calculate the zoom level that fits the
@@ -805,20 +2084,84 @@ pub enum MapError {
     /// USB Notecard
     #[error("No grid coordinates were returned for one of the regions in the USB notecard: {0}")]
     NoGridCoordinatesForRegion(RegionName),
+    /// no region names were given to determine the area of the map from
+    #[error("no region names were given to determine the area of the map from")]
+    NoRegionNames,
+    /// the maps to compare differ in zoom level, grid rectangle or size
+    #[error("the maps to compare differ in zoom level, grid rectangle or size")]
+    MismatchedMaps,
     /// error in region name to grid coordinate cache
+    #[cfg(feature = "network")]
     #[error("error in region name to grid coordinate cache: {0}")]
     RegionNameToGridCoordinateCacheError(#[from] crate::region::CacheError),
 }
 
-impl Map {
-    /// creates a new `Map`
-    ///
-    /// if we choose not to fill the missing map tiles they appear as black
-    ///
-    /// if we choose not to fill the missing regions they appear in a color
-    /// similar to water but filling them in has some performance impact since
+/// information about a rendered `Map` stored next to the saved image so
+/// tools can reload the context of a previously rendered map
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MapMetadata {
+    /// the lower left corner of the grid rectangle covered by the map
+    lower_left_corner: GridCoordinates,
+    /// the upper right corner of the grid rectangle covered by the map
+    upper_right_corner: GridCoordinates,
+    /// the zoom level of the map
+    zoom_level: ZoomLevel,
+    /// the time at which the map was saved
+    rendered_at: std::time::SystemTime,
+}
+
+impl MapMetadata {
+    /// the grid rectangle of regions represented by the map
+    #[must_use]
+    pub fn grid_rectangle(&self) -> GridRectangle {
+        GridRectangle::new(self.lower_left_corner, self.upper_right_corner)
+    }
+
+    /// the zoom level of the map
+    #[must_use]
+    pub fn zoom_level(&self) -> ZoomLevel {
+        self.zoom_level
+    }
+
+    /// the time at which the map was saved
+    #[must_use]
+    pub fn rendered_at(&self) -> std::time::SystemTime {
+        self.rendered_at
+    }
+}
+
+/// errors that can occur while saving a map with its metadata
+#[derive(Debug, thiserror::Error)]
+pub enum MapMetadataError {
+    /// error saving the map image
+    #[error("error saving the map image: {0}")]
+    ImageError(#[from] image::ImageError),
+    /// error writing the metadata file
+    #[error("error writing the metadata file: {0}")]
+    IoError(#[from] std::io::Error),
+    /// error serializing the metadata
+    #[error("error serializing the metadata: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// the path of the JSON file with the metadata for the map image at the
+/// given path, e.g. `map.png.json` for `map.png`
+fn metadata_path(path: &std::path::Path) -> std::path::PathBuf {
+    let mut metadata_path = path.as_os_str().to_owned();
+    metadata_path.push(".json");
+    std::path::PathBuf::from(metadata_path)
+}
+
+impl Map {
+    /// creates a new `Map`
+    ///
+    /// if we choose not to fill the missing map tiles they appear as black
+    ///
+    /// if we choose not to fill the missing regions they appear in a color
+    /// similar to water but filling them in has some performance impact since
     /// we need to check if the region exists by fetching higher resolutio
-    /// map tiles for it.
+    /// map tiles for it. The fill color for missing regions is blended over
+    /// the map, so a semi-transparent color only tints them.
     ///
     /// # Errors
     ///
@@ -830,6 +2173,7 @@ impl Map {
     /// * `x` - the width of the map in pixels
     /// * `y` - the height of the map in pixels
     /// * `grid_rectangle` - the grid rectangle of regions represented by this map
+    #[cfg(feature = "network")]
     pub async fn new(
         map_tile_cache: &mut MapTileCache,
         x: u32,
@@ -838,20 +2182,73 @@ impl Map {
         fill_missing_map_tiles: Option<image::Rgba<u8>>,
         fill_missing_regions: Option<image::Rgba<u8>>,
     ) -> Result<Self, MapError> {
-        let zoom_level = ZoomLevel::max_zoom_level_to_fit_regions_into_output_image(
-            grid_rectangle.size_x(),
-            grid_rectangle.size_y(),
+        Self::assemble(
+            map_tile_cache,
             x,
             y,
-        )?;
-        let actual_x = <u16 as Into<u32>>::into(zoom_level.pixels_per_region())
-            * <u16 as Into<u32>>::into(grid_rectangle.size_x());
-        let actual_y = <u16 as Into<u32>>::into(zoom_level.pixels_per_region())
-            * <u16 as Into<u32>>::into(grid_rectangle.size_y());
+            grid_rectangle,
+            fill_missing_map_tiles,
+            fill_missing_regions,
+            image::DynamicImage::new_rgb8,
+        )
+        .await
+    }
+
+    /// creates a new `Map` like `Map::new` but backed by an RGBA image
+    ///
+    /// `Map::new` uses an RGB image which ignores the alpha channel of
+    /// anything drawn onto it. An RGBA image keeps transparency (e.g. for
+    /// semi-transparent fills or overlays that are composited later) at the
+    /// cost of a third more memory. The alpha channel is only preserved when
+    /// saving in a format that supports it like PNG, not in JPEG.
+    ///
+    /// # Errors
+    ///
+    /// returns an error if fetching the map tiles fails
+    #[cfg(feature = "network")]
+    pub async fn new_rgba(
+        map_tile_cache: &mut MapTileCache,
+        x: u32,
+        y: u32,
+        grid_rectangle: GridRectangle,
+        fill_missing_map_tiles: Option<image::Rgba<u8>>,
+        fill_missing_regions: Option<image::Rgba<u8>>,
+    ) -> Result<Self, MapError> {
+        Self::assemble(
+            map_tile_cache,
+            x,
+            y,
+            grid_rectangle,
+            fill_missing_map_tiles,
+            fill_missing_regions,
+            image::DynamicImage::new_rgba8,
+        )
+        .await
+    }
+
+    /// assembles a new `Map` from map tiles into an image created with the
+    /// given function
+    ///
+    /// see `Map::new` for details
+    ///
+    /// # Errors
+    ///
+    /// returns an error if fetching the map tiles fails
+    #[cfg(feature = "network")]
+    async fn assemble(
+        map_tile_cache: &mut MapTileCache,
+        x: u32,
+        y: u32,
+        grid_rectangle: GridRectangle,
+        fill_missing_map_tiles: Option<image::Rgba<u8>>,
+        fill_missing_regions: Option<image::Rgba<u8>>,
+        new_image: fn(u32, u32) -> image::DynamicImage,
+    ) -> Result<Self, MapError> {
+        let (actual_x, actual_y, zoom_level) = Self::computed_dimensions(&grid_rectangle, x, y)?;
         tracing::debug!("Determined max zoom level for map of size ({x}, {y}) for {grid_rectangle:?} to be {zoom_level:?}, actual map size will be ({actual_x}, {actual_y})");
         let x = actual_x;
         let y = actual_y;
-        let image = image::DynamicImage::new_rgb8(x, y);
+        let image = new_image(x, y);
         let mut result = Self {
             zoom_level,
             grid_rectangle,
@@ -917,15 +2314,15 @@ impl Map {
                                     if let (Some((min_x, min_y)), Some((max_x, max_y))) =
                                         (pixel_min, pixel_max)
                                     {
-                                        for x in min_x..max_x {
-                                            for y in min_y..max_y {
-                                                <Map as image::GenericImage>::put_pixel(
-                                                    &mut result,
-                                                    x,
-                                                    y,
-                                                    fill_color,
-                                                );
-                                            }
+                                        if max_x > min_x && max_y > min_y {
+                                            result.blend_rect(
+                                                imageproc::rect::Rect::at(
+                                                    min_x as i32,
+                                                    min_y as i32,
+                                                )
+                                                .of_size(max_x - min_x, max_y - min_y),
+                                                fill_color,
+                                            );
                                         }
                                     }
                                 }
@@ -954,41 +2351,308 @@ impl Map {
         Ok(result)
     }
 
+    /// creates a new `Map` showing all the regions with the given names
+    ///
+    /// the map covers the bounding rectangle of the regions expanded by
+    /// `margin` regions in every direction
+    ///
+    /// # Errors
+    ///
+    /// returns an error if no region names were given, if one of the region
+    /// names could not be resolved to grid coordinates or if assembling the
+    /// map fails
+    ///
+    /// # Arguments
+    ///
+    /// * `map_tile_cache` - the map tile cache to use to fetch the map tiles
+    /// * `region_name_to_grid_coordinates_cache` - the cache used to resolve the region names
+    /// * `x` - the width of the map in pixels
+    /// * `y` - the height of the map in pixels
+    /// * `region_names` - the names of the regions the map should contain
+    /// * `margin` - the number of additional regions to show around the bounding rectangle
+    #[cfg(feature = "network")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn from_region_names<B: RegionLookupBackend>(
+        map_tile_cache: &mut MapTileCache,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        x: u32,
+        y: u32,
+        region_names: &[RegionName],
+        margin: u16,
+        fill_missing_map_tiles: Option<image::Rgba<u8>>,
+        fill_missing_regions: Option<image::Rgba<u8>>,
+    ) -> Result<Self, MapError> {
+        let resolved_region_names = region_name_to_grid_coordinates_cache
+            .get_grid_coordinates_batch(
+                region_names,
+                crate::region::DEFAULT_MAX_CONCURRENT_REGION_LOOKUPS,
+            )
+            .await?;
+        let mut grid_coordinates = Vec::new();
+        for region_name in region_names {
+            let Some(Some(region_grid_coordinates)) = resolved_region_names.get(region_name) else {
+                return Err(MapError::NoGridCoordinatesForRegion(region_name.to_owned()));
+            };
+            grid_coordinates.push(*region_grid_coordinates);
+        }
+        let bounding_rectangle = grid_coordinates
+            .bounding_rectangle()
+            .ok_or(MapError::NoRegionNames)?;
+        let grid_rectangle = GridRectangle::new(
+            GridCoordinates::new(
+                bounding_rectangle
+                    .lower_left_corner()
+                    .x()
+                    .saturating_sub(margin),
+                bounding_rectangle
+                    .lower_left_corner()
+                    .y()
+                    .saturating_sub(margin),
+            ),
+            GridCoordinates::new(
+                bounding_rectangle
+                    .upper_right_corner()
+                    .x()
+                    .saturating_add(margin),
+                bounding_rectangle
+                    .upper_right_corner()
+                    .y()
+                    .saturating_add(margin),
+            ),
+        );
+        Self::new(
+            map_tile_cache,
+            x,
+            y,
+            grid_rectangle,
+            fill_missing_map_tiles,
+            fill_missing_regions,
+        )
+        .await
+    }
+
+    /// creates a new `Map` centered on the region containing the given
+    /// `Location`
+    ///
+    /// the map covers a square of `2 * region_radius + 1` regions on each
+    /// side (less if it would extend beyond the edge of the grid)
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the region name of the location could not be
+    /// resolved to grid coordinates or if assembling the map fails
+    ///
+    /// # Arguments
+    ///
+    /// * `map_tile_cache` - the map tile cache to use to fetch the map tiles
+    /// * `region_name_to_grid_coordinates_cache` - the cache used to resolve the region name
+    /// * `center` - the location the map should be centered on
+    /// * `region_radius` - the number of regions to show around the center region in every direction
+    /// * `x` - the width of the map in pixels
+    /// * `y` - the height of the map in pixels
+    #[cfg(feature = "network")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn centered_on<B: RegionLookupBackend>(
+        map_tile_cache: &mut MapTileCache,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        center: &sl_types::map::Location,
+        region_radius: u16,
+        x: u32,
+        y: u32,
+        fill_missing_map_tiles: Option<image::Rgba<u8>>,
+        fill_missing_regions: Option<image::Rgba<u8>>,
+    ) -> Result<Self, MapError> {
+        let Some(center_grid_coordinates) = region_name_to_grid_coordinates_cache
+            .get_grid_coordinates(center.region_name())
+            .await?
+        else {
+            return Err(MapError::NoGridCoordinatesForRegion(
+                center.region_name().to_owned(),
+            ));
+        };
+        let grid_rectangle = GridRectangle::new(
+            GridCoordinates::new(
+                center_grid_coordinates.x().saturating_sub(region_radius),
+                center_grid_coordinates.y().saturating_sub(region_radius),
+            ),
+            GridCoordinates::new(
+                center_grid_coordinates.x().saturating_add(region_radius),
+                center_grid_coordinates.y().saturating_add(region_radius),
+            ),
+        );
+        Self::new(
+            map_tile_cache,
+            x,
+            y,
+            grid_rectangle,
+            fill_missing_map_tiles,
+            fill_missing_regions,
+        )
+        .await
+    }
+
+    /// creates a new `Map` showing the route in a `USBNotecard` and draws
+    /// the route on it
+    ///
+    /// the map covers the bounding rectangle of the regions of all the
+    /// waypoints expanded by `margin` regions in every direction
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the notecard has no waypoints, if one of the
+    /// region names could not be resolved to grid coordinates or if
+    /// assembling the map fails
+    ///
+    /// # Arguments
+    ///
+    /// * `map_tile_cache` - the map tile cache to use to fetch the map tiles
+    /// * `region_name_to_grid_coordinates_cache` - the cache used to resolve the region names
+    /// * `usb_notecard` - the route to show on the map
+    /// * `x` - the maximum width of the map in pixels
+    /// * `y` - the maximum height of the map in pixels
+    /// * `margin` - the number of additional regions to show around the route
+    /// * `color` - the color to draw the route in
+    #[cfg(feature = "network")]
+    pub async fn render_route<B: RegionLookupBackend>(
+        map_tile_cache: &mut MapTileCache,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        usb_notecard: &USBNotecard,
+        x: u32,
+        y: u32,
+        margin: u16,
+        color: image::Rgba<u8>,
+    ) -> Result<Self, MapError> {
+        let region_names = usb_notecard
+            .waypoints()
+            .iter()
+            .map(|waypoint| waypoint.location().region_name().to_owned())
+            .collect::<Vec<_>>();
+        let mut map = Self::from_region_names(
+            map_tile_cache,
+            region_name_to_grid_coordinates_cache,
+            x,
+            y,
+            &region_names,
+            margin,
+            None,
+            None,
+        )
+        .await?;
+        map.draw_route(region_name_to_grid_coordinates_cache, usb_notecard, color)
+            .await?;
+        Ok(map)
+    }
+
+    /// resolves the waypoints of a `USBNotecard` to pixel coordinates on
+    /// this map without drawing anything
+    ///
+    /// The pixel coordinates are `None` for waypoints outside of the map
+    ///
+    /// # Errors
+    ///
+    /// fails if the region name to grid coordinate conversion fails
+    #[cfg(feature = "network")]
+    pub async fn route_pixels<B: RegionLookupBackend>(
+        &self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        usb_notecard: &USBNotecard,
+    ) -> Result<Vec<(USBWaypoint, Option<(u32, u32)>)>, MapError> {
+        Ok(
+            route_grid_coordinates(region_name_to_grid_coordinates_cache, usb_notecard)
+                .await?
+                .into_iter()
+                .map(|(waypoint, grid_coordinates)| {
+                    let pixel_coordinates = self.pixel_coordinates_for_coordinates(
+                        &grid_coordinates,
+                        &waypoint.region_coordinates(),
+                    );
+                    (waypoint, pixel_coordinates)
+                })
+                .collect(),
+        )
+    }
+
+    /// resolves the waypoints of a `USBNotecard` to sub-pixel coordinates on
+    /// this map without drawing anything
+    ///
+    /// The pixel coordinates are `None` for waypoints outside of the map
+    ///
+    /// # Errors
+    ///
+    /// fails if the region name to grid coordinate conversion fails
+    #[cfg(feature = "network")]
+    pub async fn route_pixels_f32<B: RegionLookupBackend>(
+        &self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        usb_notecard: &USBNotecard,
+    ) -> Result<Vec<(USBWaypoint, Option<(f32, f32)>)>, MapError> {
+        Ok(
+            route_grid_coordinates(region_name_to_grid_coordinates_cache, usb_notecard)
+                .await?
+                .into_iter()
+                .map(|(waypoint, grid_coordinates)| {
+                    let pixel_coordinates = self.pixel_coordinates_for_coordinates_f32(
+                        &grid_coordinates,
+                        &waypoint.region_coordinates(),
+                    );
+                    (waypoint, pixel_coordinates)
+                })
+                .collect(),
+        )
+    }
+
     /// draws a route from a `USBNotecard` onto the map
     ///
     /// # Errors
     ///
     /// fails if the region name to grid coordinate conversion fails
     /// or the conversion of those into pixel coordinates
-    pub async fn draw_route(
+    #[cfg(feature = "network")]
+    pub async fn draw_route<B: RegionLookupBackend>(
         &mut self,
-        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
         usb_notecard: &USBNotecard,
         color: image::Rgba<u8>,
+    ) -> Result<(), MapError> {
+        self.draw_route_styled(
+            region_name_to_grid_coordinates_cache,
+            usb_notecard,
+            color,
+            LineStyle::Solid,
+        )
+        .await
+    }
+
+    /// draws a route from a `USBNotecard` onto the map in the given style
+    ///
+    /// the arrows at the waypoints are always drawn, even if they fall into
+    /// a gap of a dashed line
+    ///
+    /// # Errors
+    ///
+    /// fails if the region name to grid coordinate conversion fails
+    /// or the conversion of those into pixel coordinates
+    #[cfg(feature = "network")]
+    pub async fn draw_route_styled<B: RegionLookupBackend>(
+        &mut self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        usb_notecard: &USBNotecard,
+        color: image::Rgba<u8>,
+        style: LineStyle,
     ) -> Result<(), MapError> {
         tracing::debug!("Drawing route:\n{:#?}", usb_notecard);
         let mut pixel_waypoints = Vec::new();
-        for waypoint in usb_notecard.waypoints() {
-            let Some(grid_coordinates) = region_name_to_grid_coordinates_cache
-                .get_grid_coordinates(waypoint.location().region_name())
-                .await?
-            else {
-                return Err(MapError::NoGridCoordinatesForRegion(
-                    waypoint.location().region_name().to_owned(),
-                ));
-            };
-            let (x, y) = self
-                .pixel_coordinates_for_coordinates(
-                    &grid_coordinates,
-                    &waypoint.region_coordinates(),
-                )
-                .ok_or(MapError::MapCoordinateError)?;
+        for (waypoint, pixel_coordinates) in self
+            .route_pixels_f32(region_name_to_grid_coordinates_cache, usb_notecard)
+            .await?
+        {
+            let (x, y) = pixel_coordinates.ok_or(MapError::MapCoordinateError)?;
             tracing::debug!(
                 "Drawing waypoint at ({x}, {y}) for location {:?}",
                 waypoint.location()
             );
-            //self.draw_waypoint(x, y, color);
-            pixel_waypoints.push((x as f32, y as f32));
+            //self.draw_waypoint_f32(x, y, color);
+            pixel_waypoints.push((x, y));
         }
         let waypoint_count = pixel_waypoints.len();
         if waypoint_count < 2 {
@@ -1029,6 +2693,8 @@ impl Map {
             ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
         };
         let mut last_point: Option<(f32, f32)> = None;
+        let mut last_sample_point: Option<(f32, f32)> = None;
+        let mut distance_along_route = 0f32;
         for (i, waypoint) in pixel_waypoints.iter().enumerate().take(waypoint_count - 1) {
             tracing::debug!("Waypoint {}: {:?}", i, waypoint);
             let v = spline_value_for_waypoint(i);
@@ -1049,6 +2715,14 @@ impl Map {
                     let v = v - spline_value_between_waypoints
                         * (j as f32 / (samples_between_last_waypoint_and_this_one as f32 - 2f32));
                     let sample_point = sample(v);
+                    if let Some(last_sample_point) = last_sample_point {
+                        distance_along_route +=
+                            distance_between_points(sample_point, last_sample_point);
+                    }
+                    last_sample_point = Some(sample_point);
+                    if !style.is_drawn_at(distance_along_route) {
+                        continue;
+                    }
                     imageproc::drawing::draw_filled_rect_mut(
                         self.image_mut(),
                         imageproc::rect::Rect::at(
@@ -1070,213 +2744,2178 @@ impl Map {
         Ok(())
     }
 
-    /// saves the map to the specified path
-    ///
-    /// # Errors
-    ///
-    /// returns an error when the image libraries returns an error
-    /// when saving the image
-    pub fn save(&self, path: &std::path::Path) -> Result<(), image::ImageError> {
-        self.image.save(path)
-    }
-}
-
-impl GridRectangleLike for Map {
-    fn grid_rectangle(&self) -> GridRectangle {
-        self.grid_rectangle.to_owned()
+    /// draw the outline of a parcel given by the coordinates of its corners
+    /// in region-local meters, e.g. from parcel data fetched elsewhere
+    ///
+    /// the outline is closed by connecting the last corner to the first
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the region does not exist, is not on this map
+    /// or if converting the region name to grid coordinates fails
+    #[cfg(feature = "network")]
+    pub async fn draw_parcel_outline<B: RegionLookupBackend>(
+        &mut self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        region_name: &RegionName,
+        corners: &[(f32, f32)],
+        color: image::Rgba<u8>,
+    ) -> Result<(), MapError> {
+        let grid_coordinates = region_name_to_grid_coordinates_cache
+            .get_grid_coordinates(region_name)
+            .await?
+            .ok_or_else(|| MapError::NoGridCoordinatesForRegion(region_name.to_owned()))?;
+        let pixel_corners = corners
+            .iter()
+            .map(|(x, y)| {
+                self.pixel_coordinates_for_coordinates_f32(
+                    &grid_coordinates,
+                    &RegionCoordinates::new(*x, *y, 0f32),
+                )
+                .ok_or(MapError::MapCoordinateError)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if pixel_corners.len() < 2 {
+            return Ok(());
+        }
+        for (i, from) in pixel_corners.iter().enumerate() {
+            let to = pixel_corners[(i + 1) % pixel_corners.len()];
+            self.draw_line_f32(*from, to, color);
+        }
+        Ok(())
+    }
+
+    /// draws a heatmap of weighted locations onto the map
+    ///
+    /// the weights of all locations are accumulated into a density per pixel
+    /// (spread out around each location) which is then normalized and
+    /// blended onto the map using the colors of the gradient, from the
+    /// lowest density to the highest, the alpha value of the gradient colors
+    /// is scaled with the density
+    ///
+    /// locations outside of the map are ignored
+    ///
+    /// # Errors
+    ///
+    /// fails if the region name to grid coordinate conversion fails
+    #[cfg(feature = "network")]
+    pub async fn draw_heatmap<B: RegionLookupBackend>(
+        &mut self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+        points: &[(sl_types::map::Location, f32)],
+        gradient: &[image::Rgba<u8>],
+    ) -> Result<(), MapError> {
+        let mut pixel_points = Vec::new();
+        for (location, weight) in points {
+            let Some(grid_coordinates) = region_name_to_grid_coordinates_cache
+                .get_grid_coordinates(location.region_name())
+                .await?
+            else {
+                return Err(MapError::NoGridCoordinatesForRegion(
+                    location.region_name().to_owned(),
+                ));
+            };
+            let region_coordinates =
+                RegionCoordinates::new(f32::from(location.x()), f32::from(location.y()), 0f32);
+            if let Some(pixel_coordinates) =
+                self.pixel_coordinates_for_coordinates_f32(&grid_coordinates, &region_coordinates)
+            {
+                pixel_points.push((pixel_coordinates, *weight));
+            }
+        }
+        self.blend_heatmap(&pixel_points, gradient);
+        Ok(())
+    }
+
+    /// blends a heatmap of weighted pixel coordinates onto the map
+    ///
+    /// this works like `draw_heatmap` but on pixel coordinates which are
+    /// already known, e.g. from `pixel_coordinates_for_coordinates_f32`,
+    /// so it does not need any region name lookups
+    pub fn blend_heatmap(
+        &mut self,
+        pixel_points: &[((f32, f32), f32)],
+        gradient: &[image::Rgba<u8>],
+    ) {
+        /// the radius in pixels around each point its weight is spread over
+        const HEATMAP_RADIUS: f32 = 16f32;
+        if gradient.is_empty() {
+            return;
+        }
+        let (width, height) = self.dimensions();
+        let mut density = vec![0f32; width as usize * height as usize];
+        for ((point_x, point_y), weight) in pixel_points {
+            let min_x = (point_x - HEATMAP_RADIUS).floor().max(0f32) as u32;
+            let max_x = ((point_x + HEATMAP_RADIUS).ceil() as u32).min(width);
+            let min_y = (point_y - HEATMAP_RADIUS).floor().max(0f32) as u32;
+            let max_y = ((point_y + HEATMAP_RADIUS).ceil() as u32).min(height);
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let distance = ((x as f32 + 0.5f32 - point_x).powi(2)
+                        + (y as f32 + 0.5f32 - point_y).powi(2))
+                    .sqrt();
+                    if distance < HEATMAP_RADIUS {
+                        density[y as usize * width as usize + x as usize] +=
+                            weight * (1f32 - distance / HEATMAP_RADIUS);
+                    }
+                }
+            }
+        }
+        let max_density = density.iter().copied().fold(0f32, f32::max);
+        if max_density <= 0f32 {
+            return;
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let normalized_density =
+                    density[y as usize * width as usize + x as usize] / max_density;
+                if normalized_density <= 0f32 {
+                    continue;
+                }
+                let color = sample_gradient(gradient, normalized_density);
+                let alpha = f32::from(color.0[3]) / 255f32 * normalized_density;
+                let background = self.image.get_pixel(x, y);
+                let blended = image::Rgba([
+                    blend_channel(background.0[0], color.0[0], alpha),
+                    blend_channel(background.0[1], color.0[1], alpha),
+                    blend_channel(background.0[2], color.0[2], alpha),
+                    background.0[3],
+                ]);
+                image::GenericImage::put_pixel(&mut self.image, x, y, blended);
+            }
+        }
+    }
+
+    /// blends the land for sale map layer over the map
+    ///
+    /// the land for sale map tiles covering the map are fetched at the zoom
+    /// level of the map, the pixels of the land for sale map tiles that
+    /// differ from the background color of the tile (its most common color,
+    /// the land not for sale) are blended with a fixed opacity over the
+    /// existing map image, all other pixels are left unchanged, missing
+    /// land for sale map tiles are skipped
+    ///
+    /// # Errors
+    ///
+    /// returns an error if fetching the map tiles fails
+    #[cfg(feature = "network")]
+    pub async fn with_for_sale_overlay(
+        &mut self,
+        map_tile_cache: &mut MapTileCache,
+    ) -> Result<(), MapError> {
+        /// the opacity of the land for sale map tiles blended over the map
+        const FOR_SALE_OVERLAY_OPACITY: f32 = 0.5f32;
+        for region_x in self.x_range() {
+            for region_y in self.y_range() {
+                let grid_coordinates = GridCoordinates::new(region_x, region_y);
+                let map_tile_descriptor = MapTileDescriptor::new(self.zoom_level, grid_coordinates)
+                    .with_layer(MapLayer::LandForSale);
+                let Some(overlap) = self.intersect(&map_tile_descriptor) else {
+                    return Err(MapError::NoOverlapError);
+                };
+                if overlap.lower_left_corner().x() != region_x
+                    || overlap.lower_left_corner().y() != region_y
+                {
+                    // we should have already processed this map tile when
+                    // we encountered the lower left corner of the overlap
+                    continue;
+                }
+                let Some(map_tile) = map_tile_cache.get_map_tile(&map_tile_descriptor).await?
+                else {
+                    tracing::debug!("No land for sale map tile for {map_tile_descriptor:?}");
+                    continue;
+                };
+                let Some(background) = dominant_color(&map_tile) else {
+                    continue;
+                };
+                let crop = map_tile
+                    .crop_imm_grid_rectangle(&overlap)
+                    .ok_or(MapError::MapTileCropError)?;
+                let (offset_x, offset_y) = self
+                    .pixel_coordinates_for_coordinates(
+                        &overlap.upper_left_corner(),
+                        &RegionCoordinates::new(0f32, 256f32, 0f32),
+                    )
+                    .ok_or(MapError::MapCoordinateError)?;
+                for (x, y, overlay) in (*crop).pixels() {
+                    if colors_match(overlay, background) {
+                        continue;
+                    }
+                    let alpha = FOR_SALE_OVERLAY_OPACITY * f32::from(overlay.0[3]) / 255f32;
+                    let background = self.image.get_pixel(offset_x + x, offset_y + y);
+                    let blended = image::Rgba([
+                        blend_channel(background.0[0], overlay.0[0], alpha),
+                        blend_channel(background.0[1], overlay.0[1], alpha),
+                        blend_channel(background.0[2], overlay.0[2], alpha),
+                        background.0[3],
+                    ]);
+                    image::GenericImage::put_pixel(
+                        &mut self.image,
+                        offset_x + x,
+                        offset_y + y,
+                        blended,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the actual size in pixels and the zoom level of a map of the given
+    /// grid rectangle created with the given requested size
+    ///
+    /// the actual size is usually smaller than the requested size since the
+    /// map uses the highest zoom level whose map tiles fit into the requested
+    /// size without scaling, this allows showing or allocating the actual
+    /// size before fetching any map tiles
+    ///
+    /// # Errors
+    ///
+    /// returns an error if no zoom level fits the grid rectangle into the
+    /// requested size
+    pub fn computed_dimensions(
+        grid_rectangle: &GridRectangle,
+        requested_x: u32,
+        requested_y: u32,
+    ) -> Result<(u32, u32, ZoomLevel), ZoomFitError> {
+        let zoom_level = ZoomLevel::max_zoom_level_to_fit_regions_into_output_image(
+            grid_rectangle.size_x(),
+            grid_rectangle.size_y(),
+            requested_x,
+            requested_y,
+        )?;
+        let actual_x = <u16 as Into<u32>>::into(zoom_level.pixels_per_region())
+            * <u16 as Into<u32>>::into(grid_rectangle.size_x());
+        let actual_y = <u16 as Into<u32>>::into(zoom_level.pixels_per_region())
+            * <u16 as Into<u32>>::into(grid_rectangle.size_y());
+        Ok((actual_x, actual_y, zoom_level))
+    }
+
+    /// the zoom level of the map tiles this map was assembled from
+    #[must_use]
+    pub fn zoom_level(&self) -> ZoomLevel {
+        self.zoom_level
+    }
+
+    /// the size of a region on this map in pixels (in both dimensions)
+    ///
+    /// this can be used to convert distances measured in pixels on the map
+    /// into regions or meters
+    #[must_use]
+    pub fn pixels_per_region(&self) -> u16 {
+        self.zoom_level.pixels_per_region()
+    }
+
+    /// the grid rectangle of regions represented by this map
+    ///
+    /// the same as `GridRectangleLike::grid_rectangle` but usable without
+    /// importing the trait
+    #[must_use]
+    pub fn grid_rectangle(&self) -> GridRectangle {
+        self.grid_rectangle.to_owned()
+    }
+
+    /// the regions on this map with the pixel coordinates of the top left
+    /// and bottom right pixels of their area, e.g. to build clickable
+    /// hotspots for the regions in a frontend
+    #[must_use]
+    pub fn region_grid(&self) -> Vec<(GridCoordinates, (u32, u32), (u32, u32))> {
+        let pixels_per_region = self.pixels_per_region();
+        let height = self.height();
+        let lower_left_corner = self.grid_rectangle.lower_left_corner();
+        let mut result = Vec::new();
+        for y in self.grid_rectangle.y_range().rev() {
+            for x in self.grid_rectangle.x_range() {
+                let left = u32::from(x - lower_left_corner.x()) * u32::from(pixels_per_region);
+                let bottom = height.saturating_sub(
+                    u32::from(y - lower_left_corner.y()) * u32::from(pixels_per_region),
+                );
+                let top = bottom.saturating_sub(u32::from(pixels_per_region));
+                result.push((
+                    GridCoordinates::new(x, y),
+                    (left, top),
+                    (
+                        (left + u32::from(pixels_per_region)).saturating_sub(1),
+                        bottom.saturating_sub(1),
+                    ),
+                ));
+            }
+        }
+        result
+    }
+
+    /// a per-pixel difference image between this map and another map of
+    /// the same area, e.g. to detect changes between two snapshots
+    ///
+    /// each pixel contains the largest difference of any color channel of
+    /// the two maps at that position, unchanged pixels are black
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the maps differ in zoom level, grid rectangle
+    /// or size
+    pub fn diff(&self, other: &Map) -> Result<image::GrayImage, MapError> {
+        if self.zoom_level != other.zoom_level
+            || self.grid_rectangle != other.grid_rectangle
+            || self.image.dimensions() != other.image.dimensions()
+        {
+            return Err(MapError::MismatchedMaps);
+        }
+        let (width, height) = self.image.dimensions();
+        Ok(image::GrayImage::from_fn(width, height, |x, y| {
+            let pixel = self.image.get_pixel(x, y);
+            let other_pixel = other.image.get_pixel(x, y);
+            let difference = pixel
+                .0
+                .iter()
+                .zip(other_pixel.0.iter())
+                .map(|(channel, other_channel)| channel.abs_diff(*other_channel))
+                .max()
+                .unwrap_or(0);
+            image::Luma([difference])
+        }))
+    }
+
+    /// saves the map to the specified path
+    ///
+    /// # Errors
+    ///
+    /// returns an error when the image libraries returns an error
+    /// when saving the image
+    pub fn save(&self, path: &std::path::Path) -> Result<(), image::ImageError> {
+        self.image.save(path)
+    }
+
+    /// the metadata describing this map, rendered now
+    #[must_use]
+    pub fn metadata(&self) -> MapMetadata {
+        MapMetadata {
+            lower_left_corner: self.grid_rectangle.lower_left_corner(),
+            upper_right_corner: self.grid_rectangle.upper_right_corner(),
+            zoom_level: self.zoom_level,
+            rendered_at: std::time::SystemTime::now(),
+        }
+    }
+
+    /// saves the map to the specified path and its `MapMetadata` as JSON
+    /// next to it, the metadata file has the same name as the image with
+    /// `.json` appended
+    ///
+    /// # Errors
+    ///
+    /// returns an error when saving the image or writing the metadata fails
+    pub fn save_with_metadata(&self, path: &std::path::Path) -> Result<(), MapMetadataError> {
+        self.save(path)?;
+        std::fs::write(
+            metadata_path(path),
+            serde_json::to_string_pretty(&self.metadata())?,
+        )?;
+        Ok(())
+    }
+
+    /// loads the `MapMetadata` saved by `Map::save_with_metadata` for the
+    /// map image at the given path
+    ///
+    /// returns `None` if there is no metadata file or it can not be parsed
+    #[must_use]
+    pub fn load_metadata(path: &std::path::Path) -> Option<MapMetadata> {
+        let metadata = std::fs::read_to_string(metadata_path(path)).ok()?;
+        serde_json::from_str(&metadata).ok()
+    }
+
+    /// saves the map to the specified path as a JPEG file with the given
+    /// quality (1-100) regardless of the file extension
+    ///
+    /// # Errors
+    ///
+    /// returns an error when the file can not be created or the image
+    /// libraries return an error when encoding the image
+    pub fn save_jpeg(&self, path: &std::path::Path, quality: u8) -> Result<(), image::ImageError> {
+        save_jpeg(&self.image, path, quality)
+    }
+}
+
+impl GridRectangleLike for Map {
+    fn grid_rectangle(&self) -> GridRectangle {
+        self.grid_rectangle.to_owned()
+    }
+}
+
+impl image::GenericImageView for Map {
+    type Pixel = <image::DynamicImage as image::GenericImageView>::Pixel;
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Self::Pixel {
+        self.image.get_pixel(x, y)
+    }
+}
+
+impl image::GenericImage for Map {
+    fn get_pixel_mut(&mut self, x: u32, y: u32) -> &mut Self::Pixel {
+        #[allow(deprecated)]
+        self.image.get_pixel_mut(x, y)
+    }
+
+    fn put_pixel(&mut self, x: u32, y: u32, pixel: Self::Pixel) {
+        self.image.put_pixel(x, y, pixel)
+    }
+
+    fn blend_pixel(&mut self, x: u32, y: u32, pixel: Self::Pixel) {
+        #[allow(deprecated)]
+        self.image.blend_pixel(x, y, pixel)
+    }
+}
+
+impl MapLike for Map {
+    fn zoom_level(&self) -> ZoomLevel {
+        self.zoom_level
+    }
+
+    fn image(&self) -> &image::DynamicImage {
+        &self.image
+    }
+
+    fn image_mut(&mut self) -> &mut image::DynamicImage {
+        &mut self.image
+    }
+}
+
+#[cfg(test)]
+mod drawing_test {
+    use image::GenericImageView;
+    use sl_types::map::{GridCoordinates, MapLayer, ZoomLevel};
+
+    use super::*;
+    use crate::grid_profile::GridProfile;
+
+    /// a black map of a single region at zoom level 1
+    pub(super) fn test_map() -> Result<Map, Box<dyn std::error::Error>> {
+        Ok(Map {
+            zoom_level: ZoomLevel::try_new(1)?,
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1136, 1075),
+                GridCoordinates::new(1136, 1075),
+            ),
+            image: image::DynamicImage::new_rgb8(256, 256),
+        })
+    }
+
+    /// encodes a plain black map tile of the given size as a JPEG the way
+    /// the map server would return it
+    #[cfg(feature = "network")]
+    pub(super) fn raw_test_map_tile(size: u32) -> Result<Vec<u8>, image::ImageError> {
+        let mut raw_map_tile = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut raw_map_tile, 90)
+            .encode_image(&image::DynamicImage::new_rgb8(size, size).to_rgb8())?;
+        Ok(raw_map_tile)
+    }
+
+    #[test]
+    fn test_map_save_jpeg_quality() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut map = test_map()?;
+        for x in 0..256 {
+            for y in 0..256 {
+                let value = ((x * 7 + y * 13) % 256) as u8;
+                <Map as image::GenericImage>::put_pixel(
+                    &mut map,
+                    x,
+                    y,
+                    image::Rgba([value, 255 - value, (x ^ y) as u8, 255]),
+                );
+            }
+        }
+        let low_quality_file = temp_dir.path().join("low_quality.jpg");
+        let high_quality_file = temp_dir.path().join("high_quality.jpg");
+        map.save_jpeg(&low_quality_file, 10)?;
+        map.save_jpeg(&high_quality_file, 95)?;
+        assert!(
+            std::fs::metadata(&low_quality_file)?.len()
+                < std::fs::metadata(&high_quality_file)?.len()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_region_at_pixel() -> Result<(), Box<dyn std::error::Error>> {
+        let map = Map {
+            zoom_level: ZoomLevel::try_new(1)?,
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1136, 1074),
+                GridCoordinates::new(1137, 1075),
+            ),
+            image: image::DynamicImage::new_rgb8(512, 512),
+        };
+        assert_eq!(
+            map.region_at_pixel(10, 500),
+            Some(GridCoordinates::new(1136, 1074))
+        );
+        assert_eq!(
+            map.region_at_pixel(300, 500),
+            Some(GridCoordinates::new(1137, 1074))
+        );
+        assert_eq!(
+            map.region_at_pixel(10, 10),
+            Some(GridCoordinates::new(1136, 1075))
+        );
+        assert_eq!(
+            map.region_at_pixel(300, 10),
+            Some(GridCoordinates::new(1137, 1075))
+        );
+        assert_eq!(
+            map.region_at_pixel(0, 0),
+            Some(GridCoordinates::new(1136, 1075))
+        );
+        assert_eq!(
+            map.region_at_pixel(300, 0),
+            Some(GridCoordinates::new(1137, 1075))
+        );
+        assert_eq!(
+            map.region_at_pixel(0, 511),
+            Some(GridCoordinates::new(1136, 1074))
+        );
+        assert_eq!(
+            map.region_at_pixel(0, 255),
+            Some(GridCoordinates::new(1136, 1075))
+        );
+        assert_eq!(
+            map.region_at_pixel(0, 256),
+            Some(GridCoordinates::new(1136, 1074))
+        );
+        assert_eq!(
+            map.region_at_pixel(511, 511),
+            Some(GridCoordinates::new(1137, 1074))
+        );
+        assert_eq!(map.region_at_pixel(512, 10), None);
+        assert_eq!(map.region_at_pixel(10, 512), None);
+        assert_eq!(map.region_at_pixel(512, 512), None);
+        assert_eq!(map.region_at_pixel(1000, 10), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pixel_coordinates_for_coordinates_f32() -> Result<(), Box<dyn std::error::Error>> {
+        let map = Map {
+            zoom_level: ZoomLevel::try_new(3)?,
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1136, 1074),
+                GridCoordinates::new(1137, 1075),
+            ),
+            image: image::DynamicImage::new_rgb8(128, 128),
+        };
+        for (grid_coordinates, region_coordinates) in [
+            (
+                GridCoordinates::new(1136, 1074),
+                RegionCoordinates::new(10.3f32, 20.7f32, 0f32),
+            ),
+            (
+                GridCoordinates::new(1137, 1075),
+                RegionCoordinates::new(201.9f32, 3.1f32, 0f32),
+            ),
+            (
+                GridCoordinates::new(1137, 1074),
+                RegionCoordinates::new(128f32, 128f32, 0f32),
+            ),
+        ] {
+            let (x, y) = map
+                .pixel_coordinates_for_coordinates(&grid_coordinates, &region_coordinates)
+                .ok_or("no integer pixel coordinates")?;
+            let (x_f32, y_f32) = map
+                .pixel_coordinates_for_coordinates_f32(&grid_coordinates, &region_coordinates)
+                .ok_or("no float pixel coordinates")?;
+            assert!((x as f32 - x_f32).abs() < 1f32, "{x} vs {x_f32}");
+            assert!((y as f32 - y_f32).abs() < 1f32, "{y} vs {y_f32}");
+        }
+        assert_eq!(
+            map.pixel_coordinates_for_coordinates_f32(
+                &GridCoordinates::new(1138, 1074),
+                &RegionCoordinates::new(10f32, 10f32, 0f32)
+            ),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_draw_scale_bar_lower_left() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = test_map()?;
+        let color = image::Rgba([255u8, 0u8, 0u8, 255u8]);
+        map.draw_scale_bar(Corner::LowerLeft, color, None::<&ab_glyph::FontRef<'_>>);
+        let mut drawn_pixels = 0;
+        for (x, y, pixel) in map.image().pixels() {
+            if pixel == color {
+                drawn_pixels += 1;
+                assert!(
+                    x < 128 && y >= 128,
+                    "pixel ({x}, {y}) outside of the lower left corner"
+                );
+            }
+        }
+        // one region is 256m at 256 pixels so the scale bar should be 50m long
+        assert!(drawn_pixels >= 50);
+        assert_eq!(map.image().get_pixel(10, 240), color);
+        assert_eq!(map.image().get_pixel(59, 240), color);
+        assert_ne!(map.image().get_pixel(60, 240), color);
+        Ok(())
+    }
+
+    /// a minimal TrueType font with a single glyph, a filled box, which is
+    /// used for every character
+    const TEST_FONT: &[u8] = include_bytes!("../test-data/box.ttf");
+
+    #[test]
+    fn test_map_draw_scale_bar_with_label() -> Result<(), Box<dyn std::error::Error>> {
+        let font = ab_glyph::FontRef::try_from_slice(TEST_FONT)?;
+        let background = image::Rgba([0u8, 0u8, 0u8, 255u8]);
+        let color = image::Rgba([255u8, 0u8, 0u8, 255u8]);
+        for corner in [
+            Corner::UpperLeft,
+            Corner::UpperRight,
+            Corner::LowerLeft,
+            Corner::LowerRight,
+        ] {
+            let mut map = test_map()?;
+            map.draw_scale_bar(corner, color, Some(&font));
+            let mut drawn_rows = std::collections::BTreeSet::new();
+            for (x, y, pixel) in map.image().pixels() {
+                if pixel != background {
+                    assert!(
+                        (Corner::MARGIN..256 - Corner::MARGIN).contains(&x)
+                            && (Corner::MARGIN..256 - Corner::MARGIN).contains(&y),
+                        "pixel ({x}, {y}) of the scale bar in {corner:?} outside of the margin"
+                    );
+                    drawn_rows.insert(y);
+                }
+            }
+            // the bar with its ticks is the bottom-most band of rows, the
+            // label has to be separate from it above
+            let bottom_row = *drawn_rows.last().ok_or("nothing drawn")?;
+            let bar_rows = (0..=bottom_row)
+                .rev()
+                .take_while(|row| drawn_rows.contains(row))
+                .count();
+            assert_eq!(bar_rows, 10, "bar in {corner:?} overlaps the label");
+            assert!(
+                drawn_rows.len() > bar_rows,
+                "no label drawn above the bar in {corner:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_draw_north_arrow_upper_right() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = test_map()?;
+        let color = image::Rgba([255u8, 255u8, 255u8, 255u8]);
+        map.draw_north_arrow(Corner::UpperRight, 32, color);
+        let mut drawn_pixels = 0;
+        for (x, y, pixel) in map.image().pixels() {
+            if pixel == color {
+                drawn_pixels += 1;
+                assert!(
+                    (214..246).contains(&x) && (10..42).contains(&y),
+                    "pixel ({x}, {y}) outside of the upper right corner"
+                );
+            }
+        }
+        assert!(drawn_pixels > 0);
+        // the tip of the arrow is at the top center
+        assert_eq!(map.image().get_pixel(230, 10), color);
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_blend_heatmap() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = test_map()?;
+        let gradient = [
+            image::Rgba([0u8, 0u8, 255u8, 255u8]),
+            image::Rgba([255u8, 0u8, 0u8, 255u8]),
+        ];
+        map.blend_heatmap(
+            &[
+                ((64f32, 64f32), 1f32),
+                ((64f32, 64f32), 1f32),
+                ((192f32, 192f32), 1f32),
+            ],
+            &gradient,
+        );
+        // the hottest spot is fully colored with the last gradient color
+        let hot = map.image().get_pixel(64, 64);
+        assert!(hot.0[0] > 200 && hot.0[2] < 50, "{hot:?}");
+        // the spot with half the weight is less intense
+        let warm = map.image().get_pixel(192, 192);
+        assert!(warm.0[0] < hot.0[0], "{warm:?} vs {hot:?}");
+        assert!(warm.0[0] > 0 || warm.0[2] > 0, "{warm:?}");
+        // pixels far from all points are unchanged
+        assert_eq!(
+            map.image().get_pixel(128, 10),
+            image::Rgba([0u8, 0u8, 0u8, 255u8])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_gradient() {
+        let gradient = [
+            image::Rgba([0u8, 0u8, 0u8, 0u8]),
+            image::Rgba([100u8, 200u8, 0u8, 255u8]),
+            image::Rgba([200u8, 0u8, 100u8, 255u8]),
+        ];
+        assert_eq!(sample_gradient(&gradient, 0f32), gradient[0]);
+        assert_eq!(sample_gradient(&gradient, 0.5f32), gradient[1]);
+        assert_eq!(sample_gradient(&gradient, 1f32), gradient[2]);
+        assert_eq!(
+            sample_gradient(&gradient, 0.75f32),
+            image::Rgba([150u8, 100u8, 50u8, 255u8])
+        );
+    }
+
+    #[test]
+    fn test_map_tile_url() -> Result<(), Box<dyn std::error::Error>> {
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(2)?, GridCoordinates::new(1137, 1075));
+        assert_eq!(
+            map_tile_url(
+                GridProfile::second_life_main().map_tile_base_url(),
+                &map_tile_descriptor
+            ),
+            "https://secondlife-maps-cdn.akamaized.net/map-2-1136-1074-objects.jpg"
+        );
+        assert_eq!(
+            map_tile_url(
+                GridProfile::second_life_main().map_tile_base_url(),
+                &map_tile_descriptor.with_layer(MapLayer::LandForSale)
+            ),
+            "https://secondlife-maps-cdn.akamaized.net/map-2-1136-1074-land-for-sale.jpg"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_clip_line() {
+        let min = (0f32, 0f32);
+        let max = (100f32, 100f32);
+        assert_eq!(
+            clip_line((10f32, 10f32), (90f32, 50f32), min, max),
+            Some(((10f32, 10f32), (90f32, 50f32)))
+        );
+        assert_eq!(
+            clip_line((50f32, 50f32), (150f32, 50f32), min, max),
+            Some(((50f32, 50f32), (100f32, 50f32)))
+        );
+        assert_eq!(
+            clip_line((-50f32, 0f32), (50f32, 100f32), min, max),
+            Some(((0f32, 50f32), (50f32, 100f32)))
+        );
+        assert_eq!(clip_line((150f32, 10f32), (200f32, 90f32), min, max), None);
+    }
+
+    #[test]
+    fn test_draw_line_without_length() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = Map {
+            image: image::DynamicImage::new_rgba8(256, 256),
+            ..test_map()?
+        };
+        let red = image::Rgba([255, 0, 0, 255]);
+        map.draw_line_f32((100f32, 100f32), (100f32, 100f32), red);
+        assert_eq!(map.get_pixel(100, 100), image::Rgba([0, 0, 0, 0]));
+        // only touches the corner of the clip area 5 pixels outside of the image
+        map.draw_line_f32((-10f32, 0f32), (0f32, -10f32), red);
+        assert_eq!(map.get_pixel(0, 0), image::Rgba([0, 0, 0, 0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_draw_line_beyond_map_edge() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = test_map()?;
+        let red = image::Rgba([255, 0, 0, 255]);
+        map.draw_line_f32((128f32, 128f32), (1000f32, 128f32), red);
+        assert_eq!(map.get_pixel(255, 128), red);
+        assert_eq!(map.get_pixel(200, 128), red);
+        assert_eq!(map.get_pixel(100, 128), image::Rgba([0, 0, 0, 255]));
+        // a line completely outside of the map does not change anything
+        map.draw_line_f32((300f32, 10f32), (1000f32, 10f32), red);
+        assert_eq!(map.get_pixel(255, 10), image::Rgba([0, 0, 0, 255]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_draw_line_dashed() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = test_map()?;
+        let red = image::Rgba([255, 0, 0, 255]);
+        let black = image::Rgba([0, 0, 0, 255]);
+        map.draw_line_styled(
+            (0f32, 128f32),
+            (256f32, 128f32),
+            red,
+            LineStyle::Dashed {
+                dash: 20f32,
+                gap: 20f32,
+            },
+        );
+        for x in [10, 50, 90, 130] {
+            assert_eq!(map.get_pixel(x, 128), red);
+        }
+        for x in [30, 70, 110, 150] {
+            assert_eq!(map.get_pixel(x, 128), black);
+            assert_eq!(map.get_pixel(x, 126), black);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_save_with_metadata() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let map = Map {
+            zoom_level: ZoomLevel::try_new(2)?,
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1136, 1074),
+                GridCoordinates::new(1137, 1075),
+            ),
+            image: image::DynamicImage::new_rgb8(256, 256),
+        };
+        let path = temp_dir.path().join("map.png");
+        let before = std::time::SystemTime::now();
+        map.save_with_metadata(&path)?;
+        assert!(path.exists());
+        let metadata = Map::load_metadata(&path).ok_or("expected metadata")?;
+        assert_eq!(metadata.grid_rectangle(), map.grid_rectangle());
+        assert_eq!(metadata.zoom_level(), map.zoom_level());
+        assert!(metadata.rendered_at() >= before);
+        assert_eq!(Map::load_metadata(&temp_dir.path().join("other.png")), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_diff() -> Result<(), Box<dyn std::error::Error>> {
+        let map = test_map()?;
+        let mut changed_map = map.clone();
+        changed_map.draw_waypoint(128, 128, image::Rgba([255, 0, 0, 255]));
+        let diff = map.diff(&changed_map)?;
+        assert_eq!(diff.dimensions(), (256, 256));
+        assert_eq!(diff.get_pixel(128, 128), &image::Luma([255]));
+        assert_eq!(diff.get_pixel(10, 10), &image::Luma([0]));
+        assert!(map.diff(&map)?.pixels().all(|pixel| pixel.0 == [0]));
+        let other_area = Map {
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1137, 1075),
+                GridCoordinates::new(1137, 1075),
+            ),
+            ..map.clone()
+        };
+        assert!(matches!(
+            map.diff(&other_area),
+            Err(MapError::MismatchedMaps)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_region_grid() -> Result<(), Box<dyn std::error::Error>> {
+        let map = Map {
+            zoom_level: ZoomLevel::try_new(2)?,
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1136, 1074),
+                GridCoordinates::new(1137, 1075),
+            ),
+            image: image::DynamicImage::new_rgb8(256, 256),
+        };
+        assert_eq!(
+            map.region_grid(),
+            vec![
+                (GridCoordinates::new(1136, 1075), (0, 0), (127, 127)),
+                (GridCoordinates::new(1137, 1075), (128, 0), (255, 127)),
+                (GridCoordinates::new(1136, 1074), (0, 128), (127, 255)),
+                (GridCoordinates::new(1137, 1074), (128, 128), (255, 255)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_accessors() -> Result<(), Box<dyn std::error::Error>> {
+        let grid_rectangle = GridRectangle::new(
+            GridCoordinates::new(1136, 1074),
+            GridCoordinates::new(1137, 1075),
+        );
+        let map = Map {
+            zoom_level: ZoomLevel::try_new(3)?,
+            grid_rectangle: grid_rectangle.clone(),
+            image: image::DynamicImage::new_rgb8(128, 128),
+        };
+        assert_eq!(map.zoom_level(), ZoomLevel::try_new(3)?);
+        assert_eq!(map.pixels_per_region(), 64);
+        assert_eq!(map.grid_rectangle(), grid_rectangle);
+        assert_eq!(
+            u32::from(map.pixels_per_region()) * u32::from(grid_rectangle.size_x()),
+            map.width()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rescale_pixel() -> Result<(), Box<dyn std::error::Error>> {
+        let grid_rectangle = GridRectangle::new(
+            GridCoordinates::new(1136, 1074),
+            GridCoordinates::new(1137, 1075),
+        );
+        let overview_map = Map {
+            zoom_level: ZoomLevel::try_new(2)?,
+            grid_rectangle: grid_rectangle.clone(),
+            image: image::DynamicImage::new_rgb8(256, 256),
+        };
+        let detail_map = Map {
+            zoom_level: ZoomLevel::try_new(1)?,
+            grid_rectangle,
+            image: image::DynamicImage::new_rgb8(512, 512),
+        };
+        assert_eq!(
+            overview_map.rescale_pixel(100, 100, &detail_map),
+            Some((200, 200))
+        );
+        assert_eq!(
+            detail_map.rescale_pixel(200, 200, &overview_map),
+            Some((100, 100))
+        );
+        assert_eq!(overview_map.rescale_pixel(300, 100, &detail_map), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blend_rect() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = test_map()?;
+        map.blend_rect(
+            imageproc::rect::Rect::at(0, 0).of_size(256, 256),
+            image::Rgba([0, 0, 255, 255]),
+        );
+        map.blend_rect(
+            imageproc::rect::Rect::at(-10, -10).of_size(30, 30),
+            image::Rgba([255, 0, 0, 128]),
+        );
+        let blended = map.get_pixel(10, 10);
+        assert!(blended.0[0].abs_diff(128) <= 1);
+        assert_eq!(blended.0[1], 0);
+        assert!(blended.0[2].abs_diff(127) <= 1);
+        assert_eq!(map.get_pixel(30, 30), image::Rgba([0, 0, 255, 255]));
+        map.blend_filled_circle((128, 128), 5, image::Rgba([255, 0, 0, 128]));
+        assert!(map.get_pixel(128, 128).0[0].abs_diff(128) <= 1);
+        assert_eq!(map.get_pixel(128, 134), image::Rgba([0, 0, 255, 255]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_draw_grid_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = Map {
+            zoom_level: ZoomLevel::try_new(2)?,
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1136, 1074),
+                GridCoordinates::new(1139, 1075),
+            ),
+            image: image::DynamicImage::new_rgba8(512, 256),
+        };
+        let red = image::Rgba([255, 0, 0, 255]);
+        let untouched = image::Rgba([0, 0, 0, 0]);
+        map.draw_grid_lines(red);
+        let pixels_per_region = map.pixels_per_region() as u32;
+        assert_eq!(pixels_per_region, 128);
+        for i in 0..4 {
+            assert_eq!(map.get_pixel(i * pixels_per_region, 50), red);
+            assert_eq!(map.get_pixel(i * pixels_per_region + 1, 50), untouched);
+        }
+        assert_eq!(map.get_pixel(511, 50), red);
+        assert_eq!(map.get_pixel(50, 0), red);
+        assert_eq!(map.get_pixel(50, 128), red);
+        assert_eq!(map.get_pixel(50, 255), red);
+        assert_eq!(map.get_pixel(50, 127), untouched);
+        assert_eq!(map.get_pixel(50, 129), untouched);
+        assert_eq!(map.get_pixel(64, 64), untouched);
+        Ok(())
+    }
+
+    #[test]
+    fn test_draw_border() -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = Map {
+            zoom_level: ZoomLevel::try_new(1)?,
+            grid_rectangle: GridRectangle::new(
+                GridCoordinates::new(1136, 1075),
+                GridCoordinates::new(1137, 1075),
+            ),
+            image: image::DynamicImage::new_rgba8(512, 256),
+        };
+        let red = image::Rgba([255, 0, 0, 255]);
+        let untouched = image::Rgba([0, 0, 0, 0]);
+        map.draw_border(
+            &GridRectangle::new(
+                GridCoordinates::new(1137, 1075),
+                GridCoordinates::new(1137, 1075),
+            ),
+            red,
+            3,
+        );
+        assert_eq!(map.get_pixel(256, 0), red);
+        assert_eq!(map.get_pixel(511, 255), red);
+        assert_eq!(map.get_pixel(258, 128), red);
+        assert_eq!(map.get_pixel(384, 2), red);
+        assert_eq!(map.get_pixel(384, 253), red);
+        assert_eq!(map.get_pixel(509, 128), red);
+        assert_eq!(map.get_pixel(259, 128), untouched);
+        assert_eq!(map.get_pixel(384, 128), untouched);
+        assert_eq!(map.get_pixel(255, 128), untouched);
+        assert_eq!(map.get_pixel(128, 0), untouched);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nearest_waypoint_to_pixel() -> Result<(), Box<dyn std::error::Error>> {
+        let route_pixels = vec![
+            ("Thorkell/10/20/0".parse::<USBWaypoint>()?, Some((10, 236))),
+            (
+                "Thorkell/200/200/0".parse::<USBWaypoint>()?,
+                Some((200, 56)),
+            ),
+            ("Da%20Boom/128/128/0".parse::<USBWaypoint>()?, None),
+        ];
+        let Some((waypoint, distance)) = nearest_waypoint_to_pixel(&route_pixels, 190, 60) else {
+            return Err("expected to find a nearest waypoint".into());
+        };
+        assert_eq!(waypoint.location().x(), 200);
+        assert!(distance < 11f32);
+        assert!(nearest_waypoint_to_pixel(&route_pixels[2..], 190, 60).is_none());
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "network"))]
+mod test {
+    use image::GenericImageView;
+    use sl_types::map::{GridCoordinates, GridCoordinatesExt as _, ZoomLevel};
+    use tracing_test::traced_test;
+
+    use super::drawing_test::{raw_test_map_tile, test_map};
+    use super::*;
+    use crate::region::StaticRegionLookupBackend;
+
+    #[tokio::test]
+    async fn test_fetch_map_tile_highest_detail() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None);
+        map_tile_cache
+            .get_map_tile(&MapTileDescriptor::new(
+                ZoomLevel::try_new(1)?,
+                GridCoordinates::new(1136, 1075),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_map_tile_highest_detail_twice() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None);
+        map_tile_cache
+            .get_map_tile(&MapTileDescriptor::new(
+                ZoomLevel::try_new(1)?,
+                GridCoordinates::new(1136, 1075),
+            ))
+            .await?;
+        map_tile_cache
+            .get_map_tile(&MapTileDescriptor::new(
+                ZoomLevel::try_new(1)?,
+                GridCoordinates::new(1136, 1075),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_map_tile_lowest_detail() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None);
+        map_tile_cache
+            .get_map_tile(&MapTileDescriptor::new(
+                ZoomLevel::try_new(8)?,
+                GridCoordinates::new(1136, 1075),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_fetch_map_zoom_level_1() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let ratelimiter =
+            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_secs(1)).build()?;
+        let mut map_tile_cache =
+            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
+        let map = Map::new(
+            &mut map_tile_cache,
+            512,
+            512,
+            GridRectangle::new(
+                GridCoordinates::new(1135, 1070),
+                GridCoordinates::new(1136, 1071),
+            ),
+            None,
+            None,
+        )
+        .await?;
+        map.save(std::path::Path::new("/tmp/test_map_zoom_level_1.jpg"))?;
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_fetch_map_zoom_level_2() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let ratelimiter =
+            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_secs(1)).build()?;
+        let mut map_tile_cache =
+            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
+        let map = Map::new(
+            &mut map_tile_cache,
+            256,
+            256,
+            GridRectangle::new(
+                GridCoordinates::new(1136, 1074),
+                GridCoordinates::new(1137, 1075),
+            ),
+            None,
+            None,
+        )
+        .await?;
+        map.save(std::path::Path::new("/tmp/test_map_zoom_level_2.jpg"))?;
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_fetch_map_zoom_level_3() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let ratelimiter =
+            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_secs(1)).build()?;
+        let mut map_tile_cache =
+            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
+        let map = Map::new(
+            &mut map_tile_cache,
+            128,
+            128,
+            GridRectangle::new(
+                GridCoordinates::new(1136, 1074),
+                GridCoordinates::new(1137, 1075),
+            ),
+            None,
+            None,
+        )
+        .await?;
+        map.save(std::path::Path::new("/tmp/test_map_zoom_level_3.jpg"))?;
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_fetch_map_zoom_level_1_ratelimiter() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let ratelimiter =
+            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_millis(100)).build()?;
+        let mut map_tile_cache =
+            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
+        let map = Map::new(
+            &mut map_tile_cache,
+            2048,
+            2048,
+            GridRectangle::new(
+                GridCoordinates::new(1131, 1068),
+                GridCoordinates::new(1139, 1075),
+            ),
+            None,
+            None,
+        )
+        .await?;
+        map.save(std::path::Path::new(
+            "/tmp/test_map_zoom_level_1_ratelimiter.jpg",
+        ))?;
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_map_route_pixels_partly_outside_map() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut region_name_to_grid_coordinates_cache =
+            RegionNameToGridCoordinatesCache::with_backend(
+                temp_dir.path().to_path_buf(),
+                StaticRegionLookupBackend::new()
+                    .with_region(
+                        RegionName::try_new("Thorkell")?,
+                        GridCoordinates::new(1136, 1075),
+                    )
+                    .with_region(
+                        RegionName::try_new("Da Boom")?,
+                        GridCoordinates::new(1000, 1000),
+                    ),
+            )?;
+        let map = test_map()?;
+        let usb_notecard: USBNotecard = "Thorkell/10/20/0\nDa%20Boom/128/128/0".parse()?;
+        let route_pixels = map
+            .route_pixels(&mut region_name_to_grid_coordinates_cache, &usb_notecard)
+            .await?;
+        assert_eq!(route_pixels.len(), 2);
+        assert_eq!(route_pixels[0].1, Some((10, 236)));
+        assert_eq!(route_pixels[1].1, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_map_from_adjacent_region_names() -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/map-\d-\d+-\d+-objects\.jpg$".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_map_tile)
+            .create_async()
+            .await;
+        let temp_dir = tempfile::tempdir()?;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let mut region_name_to_grid_coordinates_cache =
+            RegionNameToGridCoordinatesCache::with_backend(
+                temp_dir.path().to_path_buf(),
+                StaticRegionLookupBackend::new()
+                    .with_region(
+                        RegionName::try_new("Ahern")?,
+                        GridCoordinates::new(997, 1002),
+                    )
+                    .with_region(
+                        RegionName::try_new("Morris")?,
+                        GridCoordinates::new(997, 1001),
+                    ),
+            )?;
+        let region_names = vec![
+            RegionName::try_new("Ahern")?,
+            RegionName::try_new("Morris")?,
+        ];
+        let mut grid_coordinates = Vec::new();
+        for region_name in &region_names {
+            grid_coordinates.push(
+                region_name_to_grid_coordinates_cache
+                    .get_grid_coordinates(region_name)
+                    .await?
+                    .ok_or("region not found")?,
+            );
+        }
+        let map = Map::from_region_names(
+            &mut map_tile_cache,
+            &mut region_name_to_grid_coordinates_cache,
+            512,
+            512,
+            &region_names,
+            1,
+            None,
+            None,
+        )
+        .await?;
+        let bounding_rectangle = grid_coordinates
+            .bounding_rectangle()
+            .ok_or("no bounding rectangle")?;
+        assert_eq!(map.size_x(), bounding_rectangle.size_x() + 2);
+        assert_eq!(map.size_y(), bounding_rectangle.size_y() + 2);
+        for grid_coordinates in &grid_coordinates {
+            assert!(map.contains(grid_coordinates));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_map_centered_on_location() -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/map-\d-\d+-\d+-objects\.jpg$".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_map_tile)
+            .create_async()
+            .await;
+        let temp_dir = tempfile::tempdir()?;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let mut region_name_to_grid_coordinates_cache =
+            RegionNameToGridCoordinatesCache::with_backend(
+                temp_dir.path().to_path_buf(),
+                StaticRegionLookupBackend::new().with_region(
+                    RegionName::try_new("Thorkell")?,
+                    GridCoordinates::new(1136, 1075),
+                ),
+            )?;
+        let center: sl_types::map::Location = "Thorkell/128/128/0".parse()?;
+        let map = Map::centered_on(
+            &mut map_tile_cache,
+            &mut region_name_to_grid_coordinates_cache,
+            &center,
+            1,
+            768,
+            768,
+            None,
+            None,
+        )
+        .await?;
+        assert_eq!(
+            map.grid_rectangle(),
+            GridRectangle::new(
+                GridCoordinates::new(1135, 1074),
+                GridCoordinates::new(1137, 1076),
+            )
+        );
+        assert_eq!(map.size_x(), 3);
+        assert_eq!(map.size_y(), 3);
+        Ok(())
+    }
+
+    /// a `GridProfile` pointing all services at the given mock server
+    fn mock_grid_profile(server: &mockito::Server) -> GridProfile {
+        GridProfile::new(
+            server.url(),
+            format!("{}/map-block-query", server.url()),
+            format!("{}/name-service", server.url()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_cached_map_tile_matches_server_response() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let temp_dir = tempfile::tempdir()?;
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(&raw_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None)
+            .with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        mock.assert_async().await;
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("map-1-1136-1075-objects.jpg"))?,
+            raw_map_tile
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_map_with_for_sale_overlay() -> Result<(), Box<dyn std::error::Error>> {
+        let objects_color = image::Rgb([100u8, 150u8, 200u8]);
+        let for_sale_color = image::Rgb([255u8, 255u8, 0u8]);
+        let mut raw_objects_map_tile = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut raw_objects_map_tile, 90)
+            .encode_image(&image::RgbImage::from_pixel(256, 256, objects_color))?;
+        let mut raw_land_for_sale_map_tile = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut raw_land_for_sale_map_tile, 90)
+            .encode_image(&image::RgbImage::from_fn(256, 256, |x, y| {
+                if (64..128).contains(&x) && (64..128).contains(&y) {
+                    for_sale_color
+                } else {
+                    image::Rgb([0u8, 0u8, 0u8])
+                }
+            }))?;
+        let mut server = mockito::Server::new_async().await;
+        let objects_mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_objects_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let land_for_sale_mock = server
+            .mock("GET", "/map-1-1136-1075-land-for-sale.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_land_for_sale_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let mut map = Map::new(
+            &mut map_tile_cache,
+            256,
+            256,
+            GridRectangle::new(
+                GridCoordinates::new(1136, 1075),
+                GridCoordinates::new(1136, 1075),
+            ),
+            None,
+            None,
+        )
+        .await?;
+        let not_for_sale_pixel = map.get_pixel(10, 10);
+        let for_sale_pixel = map.get_pixel(96, 96);
+        map.with_for_sale_overlay(&mut map_tile_cache).await?;
+        objects_mock.assert_async().await;
+        land_for_sale_mock.assert_async().await;
+        // land not for sale is not washed out by the overlay
+        assert_eq!(map.get_pixel(10, 10), not_for_sale_pixel);
+        // land for sale is blended towards the color of the overlay
+        let blended = map.get_pixel(96, 96);
+        assert!(blended.0[0] > for_sale_pixel.0[0] + 50, "{blended:?}");
+        assert!(blended.0[2] < for_sale_pixel.0[2] - 50, "{blended:?}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_map_new_rgba_preserves_alpha() -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_map_tile)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let grid_rectangle = GridRectangle::new(
+            GridCoordinates::new(1136, 1075),
+            GridCoordinates::new(1136, 1075),
+        );
+        let semi_transparent_red = image::Rgba([255, 0, 0, 128]);
+        let mut rgba_map = Map::new_rgba(
+            &mut map_tile_cache,
+            256,
+            256,
+            grid_rectangle.clone(),
+            None,
+            None,
+        )
+        .await?;
+        assert_eq!(rgba_map.get_pixel(10, 10).0[3], 255);
+        rgba_map.draw_waypoint(128, 128, semi_transparent_red);
+        assert_eq!(rgba_map.get_pixel(128, 128), semi_transparent_red);
+        let mut rgb_map =
+            Map::new(&mut map_tile_cache, 256, 256, grid_rectangle, None, None).await?;
+        rgb_map.draw_waypoint(128, 128, semi_transparent_red);
+        assert_eq!(rgb_map.get_pixel(128, 128), image::Rgba([255, 0, 0, 255]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_map_computed_dimensions() -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/map-\d-\d+-\d+-objects\.jpg$".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_map_tile)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let grid_rectangle = GridRectangle::new(
+            GridCoordinates::new(1136, 1074),
+            GridCoordinates::new(1138, 1075),
+        );
+        let (x, y, zoom_level) = Map::computed_dimensions(&grid_rectangle, 500, 500)?;
+        assert_eq!((x, y, zoom_level), (384, 256, ZoomLevel::try_new(2)?));
+        let map = Map::new(&mut map_tile_cache, 500, 500, grid_rectangle, None, None).await?;
+        assert_eq!(map.dimensions(), (x, y));
+        assert_eq!(map.zoom_level(), zoom_level);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_map_render_route() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/map-\d-\d+-\d+-objects\.jpg$".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_map_tile)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let mut region_name_to_grid_coordinates_cache =
+            RegionNameToGridCoordinatesCache::with_backend(
+                temp_dir.path().to_path_buf(),
+                StaticRegionLookupBackend::new()
+                    .with_region(
+                        RegionName::try_new("Region A")?,
+                        GridCoordinates::new(1136, 1075),
+                    )
+                    .with_region(
+                        RegionName::try_new("Region B")?,
+                        GridCoordinates::new(1137, 1075),
+                    ),
+            )?;
+        let usb_notecard: USBNotecard =
+            "Region%20A/64/128/0\nRegion%20A/192/128/0\nRegion%20B/128/128/0".parse()?;
+        let red = image::Rgba([255, 0, 0, 255]);
+        let map = Map::render_route(
+            &mut map_tile_cache,
+            &mut region_name_to_grid_coordinates_cache,
+            &usb_notecard,
+            512,
+            512,
+            0,
+            red,
+        )
+        .await?;
+        assert_eq!(
+            map.grid_rectangle(),
+            GridRectangle::new(
+                GridCoordinates::new(1136, 1075),
+                GridCoordinates::new(1137, 1075),
+            )
+        );
+        assert_eq!(map.dimensions(), (512, 256));
+        assert!((64..192).any(|x| map.get_pixel(x, 128) == red));
+        assert_ne!(map.get_pixel(128, 10), red);
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_does_region_exist_respects_ratelimiter() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let temp_dir = tempfile::tempdir()?;
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/map-[1-8]-\d+-\d+-objects\.jpg$".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(&raw_map_tile)
+            .expect(8)
+            .create_async()
+            .await;
+        let ratelimiter = ratelimit::Ratelimiter::builder(1, std::time::Duration::from_millis(100))
+            .max_tokens(1)
+            .initial_available(1)
+            .build()?;
+        let mut map_tile_cache =
+            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter))
+                .with_grid_profile(mock_grid_profile(&server));
+        assert!(
+            map_tile_cache
+                .does_region_exist(&GridCoordinates::new(1136, 1075))
+                .await?
+        );
+        // one request per zoom level, all but the first one have to wait
+        // for the ratelimiter to refill
+        logs_assert(|lines: &[&str]| {
+            match lines
+                .iter()
+                .filter(|line| line.contains("Ratelimit reached, delaying map tile request"))
+                .count()
+            {
+                7 => Ok(()),
+                count => Err(format!("expected 7 delayed map tile requests, got {count}")),
+            }
+        });
+        mock.assert_async().await;
+        Ok(())
     }
-}
-
-impl image::GenericImageView for Map {
-    type Pixel = <image::DynamicImage as image::GenericImageView>::Pixel;
 
-    fn dimensions(&self) -> (u32, u32) {
-        self.image.dimensions()
+    #[tokio::test]
+    async fn test_does_region_exist_implausible_coordinates(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/map-[1-8]-\d+-\d+-objects\.jpg$".to_string()),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::in_memory(None).with_grid_profile(
+            mock_grid_profile(&server).with_plausible_region_bounds(
+                GridProfile::second_life_main()
+                    .plausible_region_bounds()
+                    .ok_or("expected plausible region bounds")?
+                    .to_owned(),
+            ),
+        );
+        assert!(
+            !map_tile_cache
+                .does_region_exist(&GridCoordinates::new(10, 10))
+                .await?
+        );
+        assert!(
+            !map_tile_cache
+                .does_region_exist(&GridCoordinates::new(1136, 30000))
+                .await?
+        );
+        mock.assert_async().await;
+        Ok(())
     }
 
-    fn get_pixel(&self, x: u32, y: u32) -> Self::Pixel {
-        self.image.get_pixel(x, y)
+    #[tokio::test]
+    async fn test_does_region_exist_custom_grid_without_bounds(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/map-[1-8]-10-10-objects\.jpg$".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_body(&raw_map_tile)
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        assert!(
+            map_tile_cache
+                .does_region_exist(&GridCoordinates::new(10, 10))
+                .await?
+        );
+        mock.assert_async().await;
+        Ok(())
     }
-}
 
-impl image::GenericImage for Map {
-    fn get_pixel_mut(&mut self, x: u32, y: u32) -> &mut Self::Pixel {
-        #[allow(deprecated)]
-        self.image.get_pixel_mut(x, y)
+    #[tokio::test]
+    async fn test_shared_map_tile_cache_concurrent_tasks() -> Result<(), Box<dyn std::error::Error>>
+    {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+        let temp_dir = tempfile::tempdir()?;
+        let raw_map_tile = raw_test_map_tile(256)?;
+        // a minimal map tile server which only responds once two requests
+        // are waiting for a response so the fetches can only succeed if the
+        // lock is not held while waiting for the server
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let barrier = std::sync::Arc::new(tokio::sync::Barrier::new(2));
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().await?;
+                let barrier = barrier.clone();
+                let raw_map_tile = raw_map_tile.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buffer = [0u8; 1024];
+                    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                        let length = stream.read(&mut buffer).await?;
+                        if length == 0 {
+                            break;
+                        }
+                        request.extend_from_slice(&buffer[..length]);
+                    }
+                    barrier.wait().await;
+                    stream
+                        .write_all(
+                            format!(
+                                "HTTP/1.1 200 OK\r\ncontent-type: image/jpeg\r\ncontent-length: {}\r\ncache-control: max-age=3600\r\nconnection: close\r\n\r\n",
+                                raw_map_tile.len()
+                            )
+                            .as_bytes(),
+                        )
+                        .await?;
+                    stream.write_all(&raw_map_tile).await?;
+                    stream.shutdown().await
+                });
+            }
+            Ok::<_, std::io::Error>(())
+        });
+        let shared_map_tile_cache = SharedMapTileCache::new(
+            MapTileCache::new(temp_dir.path().to_path_buf(), None).with_grid_profile(
+                GridProfile::new(
+                    format!("http://{address}"),
+                    format!("http://{address}/map-block-query"),
+                    format!("http://{address}/name-service"),
+                ),
+            ),
+        );
+        let mut tasks = Vec::new();
+        for x in [1136, 1137] {
+            let shared_map_tile_cache = shared_map_tile_cache.clone();
+            tasks.push(tokio::spawn(async move {
+                let map_tile_descriptor =
+                    MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(x, 1075));
+                Ok::<_, MapTileCacheError>(
+                    shared_map_tile_cache
+                        .get_map_tile(&map_tile_descriptor)
+                        .await?
+                        .is_some(),
+                )
+            }));
+        }
+        // if the fetches were serialized the first response would never be
+        // sent and this would time out
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            for task in tasks {
+                assert!(task.await??);
+            }
+            Ok::<_, Box<dyn std::error::Error>>(())
+        })
+        .await??;
+        server.await??;
+        Ok(())
     }
 
-    fn put_pixel(&mut self, x: u32, y: u32, pixel: Self::Pixel) {
-        self.image.put_pixel(x, y, pixel)
+    #[tokio::test]
+    async fn test_in_memory_map_tile_cache() -> Result<(), Box<dyn std::error::Error>> {
+        // relative cache paths would end up in the current directory so
+        // point it to an empty temporary directory while the cache is used
+        let temp_dir = tempfile::tempdir()?;
+        let previous_current_dir = std::env::current_dir()?;
+        std::env::set_current_dir(temp_dir.path())?;
+        let result = in_memory_map_tile_cache_requests().await;
+        std::env::set_current_dir(previous_current_dir)?;
+        result?;
+        assert_eq!(std::fs::read_dir(temp_dir.path())?.count(), 0);
+        Ok(())
     }
 
-    fn blend_pixel(&mut self, x: u32, y: u32, pixel: Self::Pixel) {
-        #[allow(deprecated)]
-        self.image.blend_pixel(x, y, pixel)
+    /// requests the same map tile twice from an in-memory `MapTileCache`
+    async fn in_memory_map_tile_cache_requests() -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(&raw_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        // the second request is served from the in-memory cache
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        mock.assert_async().await;
+        assert_eq!(
+            map_tile_cache
+                .cache_entry_status(&map_tile_descriptor)
+                .await?,
+            MapTileCacheEntryStatus::Missing
+        );
+        Ok(())
     }
-}
 
-impl MapLike for Map {
-    fn zoom_level(&self) -> ZoomLevel {
-        self.zoom_level
+    #[tokio::test]
+    async fn test_map_tile_unexpected_content_type() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::tempdir()?;
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let full_response_mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=0")
+            .with_header("etag", "\"map-tile-etag\"")
+            .with_body(&raw_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .match_header("if-none-match", "\"map-tile-etag\"")
+            .with_status(200)
+            .with_header("content-type", "text/html; charset=utf-8")
+            .with_body("<html><body>Service Unavailable</body></html>")
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None)
+            .with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        let result = map_tile_cache.get_map_tile(&map_tile_descriptor).await;
+        assert!(
+            matches!(
+                result,
+                Err(MapTileCacheError::UnexpectedContentType(ref content_type))
+                    if content_type == "text/html; charset=utf-8"
+            ),
+            "unexpected result {result:?}"
+        );
+        full_response_mock.assert_async().await;
+        mock.assert_async().await;
+        // the previously cached map tile survives the error page
+        assert_eq!(
+            map_tile_cache
+                .cache_entry_status(&map_tile_descriptor)
+                .await?,
+            MapTileCacheEntryStatus::Valid
+        );
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("map-1-1136-1075-objects.jpg"))?,
+            raw_map_tile
+        );
+        Ok(())
     }
 
-    fn image(&self) -> &image::DynamicImage {
-        &self.image
+    #[tokio::test]
+    async fn test_map_tile_content_type_case_insensitive() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "Image/JPEG")
+            .with_body(&raw_map_tile)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        Ok(())
     }
 
-    fn image_mut(&mut self) -> &mut image::DynamicImage {
-        &mut self.image
+    #[tokio::test]
+    async fn test_map_tile_missing_content_type_sniffs_image(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_body(&raw_map_tile)
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        Ok(())
     }
-}
-
-#[cfg(test)]
-mod test {
-    use image::GenericImageView;
-    use sl_types::map::{GridCoordinates, ZoomLevel};
-    use tracing_test::traced_test;
-
-    use super::*;
 
     #[tokio::test]
-    async fn test_fetch_map_tile_highest_detail() -> Result<(), Box<dyn std::error::Error>> {
-        let temp_dir = tempfile::tempdir()?;
-        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None);
-        map_tile_cache
-            .get_map_tile(&MapTileDescriptor::new(
-                ZoomLevel::try_new(1)?,
-                GridCoordinates::new(1136, 1075),
-            ))
-            .await?;
+    async fn test_map_tile_missing_content_type_not_an_image(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_body("<html><body>Service Unavailable</body></html>")
+            .create_async()
+            .await;
+        let mut map_tile_cache =
+            MapTileCache::in_memory(None).with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        let result = map_tile_cache.get_map_tile(&map_tile_descriptor).await;
+        assert!(
+            matches!(result, Err(MapTileCacheError::UnrecognizedImageData)),
+            "unexpected result {result:?}"
+        );
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_fetch_map_tile_highest_detail_twice() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test_map_tile_absent_status_code_404() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempfile::tempdir()?;
-        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None);
-        map_tile_cache
-            .get_map_tile(&MapTileDescriptor::new(
-                ZoomLevel::try_new(1)?,
-                GridCoordinates::new(1136, 1075),
-            ))
-            .await?;
-        map_tile_cache
-            .get_map_tile(&MapTileDescriptor::new(
-                ZoomLevel::try_new(1)?,
-                GridCoordinates::new(1136, 1075),
-            ))
-            .await?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(404)
+            .with_header("cache-control", "max-age=3600")
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None)
+            .with_grid_profile(mock_grid_profile(&server))
+            .with_absent_status_codes([
+                reqwest::StatusCode::FORBIDDEN,
+                reqwest::StatusCode::NOT_FOUND,
+            ]);
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_none());
+        // the absence is cached so the second request does not hit the server
+        assert!(
+            !map_tile_cache
+                .does_map_tile_exist(&map_tile_descriptor)
+                .await?
+        );
+        mock.assert_async().await;
+        assert_eq!(
+            map_tile_cache
+                .cache_entry_status(&map_tile_descriptor)
+                .await?,
+            MapTileCacheEntryStatus::Valid
+        );
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_fetch_map_tile_lowest_detail() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test_invalidate_region_refetches_map_tile() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempfile::tempdir()?;
-        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None);
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(&raw_map_tile)
+            .expect(2)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None)
+            .with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
         map_tile_cache
-            .get_map_tile(&MapTileDescriptor::new(
-                ZoomLevel::try_new(8)?,
-                GridCoordinates::new(1136, 1075),
-            ))
+            .invalidate_region(&GridCoordinates::new(1136, 1075))
             .await?;
+        assert_eq!(
+            map_tile_cache
+                .cache_entry_status(&map_tile_descriptor)
+                .await?,
+            MapTileCacheEntryStatus::Missing
+        );
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        mock.assert_async().await;
         Ok(())
     }
 
-    #[traced_test]
     #[tokio::test]
-    async fn test_fetch_map_zoom_level_1() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test_min_fresh_overrides_short_max_age() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempfile::tempdir()?;
-        let ratelimiter =
-            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_secs(1)).build()?;
-        let mut map_tile_cache =
-            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
-        let map = Map::new(
-            &mut map_tile_cache,
-            512,
-            512,
-            GridRectangle::new(
-                GridCoordinates::new(1135, 1070),
-                GridCoordinates::new(1136, 1071),
-            ),
-            None,
-            None,
-        )
-        .await?;
-        map.save(std::path::Path::new("/tmp/test_map_zoom_level_1.jpg"))?;
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=0")
+            .with_body(&raw_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None)
+            .with_grid_profile(mock_grid_profile(&server))
+            .with_min_fresh(std::time::Duration::from_secs(3600));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        assert!(
+            map_tile_cache
+                .does_map_tile_exist(&map_tile_descriptor)
+                .await?
+        );
+        mock.assert_async().await;
         Ok(())
     }
 
-    #[traced_test]
     #[tokio::test]
-    async fn test_fetch_map_zoom_level_2() -> Result<(), Box<dyn std::error::Error>> {
-        let temp_dir = tempfile::tempdir()?;
-        let ratelimiter =
-            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_secs(1)).build()?;
-        let mut map_tile_cache =
-            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
-        let map = Map::new(
-            &mut map_tile_cache,
-            256,
-            256,
-            GridRectangle::new(
-                GridCoordinates::new(1136, 1074),
-                GridCoordinates::new(1137, 1075),
-            ),
-            None,
-            None,
-        )
-        .await?;
-        map.save(std::path::Path::new("/tmp/test_map_zoom_level_2.jpg"))?;
+    async fn test_map_tile_cache_builder() -> Result<(), Box<dyn std::error::Error>> {
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let first_mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(&raw_map_tile)
+            .expect(2)
+            .create_async()
+            .await;
+        let second_mock = server
+            .mock("GET", "/map-1-1137-1075-objects.jpg")
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(&raw_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::builder()
+            .grid_profile(mock_grid_profile(&server))
+            .memory_cache_capacity(1)
+            .build()?;
+        let first =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        let second =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1137, 1075));
+        // without a cache directory and room for only one map tile in memory
+        // the first map tile has to be fetched again
+        for map_tile_descriptor in [&first, &second, &first] {
+            assert!(map_tile_cache
+                .get_map_tile(map_tile_descriptor)
+                .await?
+                .is_some());
+        }
+        first_mock.assert_async().await;
+        second_mock.assert_async().await;
         Ok(())
     }
 
-    #[traced_test]
+    #[test]
+    fn test_map_tile_cache_builder_invalid_options() {
+        assert!(matches!(
+            MapTileCache::builder().memory_cache_capacity(0).build(),
+            Err(MapTileCacheBuilderError::ZeroMemoryCacheCapacity)
+        ));
+        assert!(matches!(
+            MapTileCache::builder().absent_status_codes([]).build(),
+            Err(MapTileCacheBuilderError::NoAbsentStatusCodes)
+        ));
+    }
+
     #[tokio::test]
-    async fn test_fetch_map_zoom_level_3() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test_revalidate_stale_map_tile_not_modified() -> Result<(), Box<dyn std::error::Error>>
+    {
         let temp_dir = tempfile::tempdir()?;
-        let ratelimiter =
-            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_secs(1)).build()?;
-        let mut map_tile_cache =
-            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
-        let map = Map::new(
-            &mut map_tile_cache,
-            128,
-            128,
-            GridRectangle::new(
-                GridCoordinates::new(1136, 1074),
-                GridCoordinates::new(1137, 1075),
-            ),
-            None,
-            None,
-        )
-        .await?;
-        map.save(std::path::Path::new("/tmp/test_map_zoom_level_3.jpg"))?;
+        let raw_map_tile = raw_test_map_tile(256)?;
+        let mut server = mockito::Server::new_async().await;
+        let full_response_mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "image/jpeg")
+            .with_header("cache-control", "max-age=0")
+            .with_header("etag", "\"map-tile-etag\"")
+            .with_body(&raw_map_tile)
+            .expect(1)
+            .create_async()
+            .await;
+        let not_modified_mock = server
+            .mock("GET", "/map-1-1136-1075-objects.jpg")
+            .match_header("if-none-match", "\"map-tile-etag\"")
+            .with_status(304)
+            .with_header("cache-control", "max-age=0")
+            .with_header("etag", "\"map-tile-etag\"")
+            .expect(1)
+            .create_async()
+            .await;
+        let mut map_tile_cache = MapTileCache::new(temp_dir.path().to_path_buf(), None)
+            .with_grid_profile(mock_grid_profile(&server));
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        assert!(map_tile_cache
+            .get_map_tile(&map_tile_descriptor)
+            .await?
+            .is_some());
+        full_response_mock.assert_async().await;
+        not_modified_mock.assert_async().await;
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("map-1-1136-1075-objects.jpg"))?,
+            raw_map_tile
+        );
         Ok(())
     }
 
-    #[traced_test]
     #[tokio::test]
-    async fn test_fetch_map_zoom_level_1_ratelimiter() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test_draw_parcel_outline() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempfile::tempdir()?;
-        let ratelimiter =
-            ratelimit::Ratelimiter::builder(1, std::time::Duration::from_millis(100)).build()?;
-        let mut map_tile_cache =
-            MapTileCache::new(temp_dir.path().to_path_buf(), Some(ratelimiter));
-        let map = Map::new(
-            &mut map_tile_cache,
-            2048,
-            2048,
-            GridRectangle::new(
-                GridCoordinates::new(1131, 1068),
-                GridCoordinates::new(1139, 1075),
-            ),
-            None,
-            None,
+        let mut region_name_to_grid_coordinates_cache =
+            RegionNameToGridCoordinatesCache::with_backend(
+                temp_dir.path().to_path_buf(),
+                StaticRegionLookupBackend::new().with_region(
+                    RegionName::try_new("Thorkell")?,
+                    GridCoordinates::new(1136, 1075),
+                ),
+            )?;
+        let mut map = Map {
+            image: image::DynamicImage::new_rgba8(256, 256),
+            ..test_map()?
+        };
+        let red = image::Rgba([255, 0, 0, 255]);
+        map.draw_parcel_outline(
+            &mut region_name_to_grid_coordinates_cache,
+            &RegionName::try_new("Thorkell")?,
+            &[
+                (64f32, 64f32),
+                (128f32, 64f32),
+                (128f32, 128f32),
+                (64f32, 128f32),
+            ],
+            red,
         )
         .await?;
-        map.save(std::path::Path::new(
-            "/tmp/test_map_zoom_level_1_ratelimiter.jpg",
-        ))?;
+        assert_eq!(map.get_pixel(96, 192), red);
+        assert_eq!(map.get_pixel(128, 160), red);
+        assert_eq!(map.get_pixel(96, 128), red);
+        assert_eq!(map.get_pixel(64, 160), red);
+        assert_eq!(map.get_pixel(96, 160), image::Rgba([0, 0, 0, 0]));
         Ok(())
     }
 
@@ -1451,4 +5090,47 @@ mod test {
         }
         Ok(())
     }
+
+    proptest::proptest! {
+        #[test]
+        fn test_pixel_coordinates_round_trip(
+            zoom_level in proptest::prelude::any::<ZoomLevel>(),
+            grid_coordinates in proptest::prelude::any::<GridCoordinates>(),
+            region_coordinates in proptest::prelude::any::<RegionCoordinates>(),
+        ) {
+            let map_tile_descriptor = MapTileDescriptor::new(zoom_level, grid_coordinates);
+            let map_tile = MapTile {
+                image: image::DynamicImage::new_rgb8(
+                    map_tile_descriptor.tile_size_in_pixels(),
+                    map_tile_descriptor.tile_size_in_pixels(),
+                ),
+                descriptor: map_tile_descriptor,
+            };
+            let (pixel_x, pixel_y) = map_tile
+                .pixel_coordinates_for_coordinates(&grid_coordinates, &region_coordinates)
+                .ok_or_else(|| proptest::test_runner::TestCaseError::fail("no pixel coordinates"))?;
+            let (result_grid_coordinates, result_region_coordinates) = map_tile
+                .coordinates_for_pixel_coordinates(pixel_x, pixel_y)
+                .ok_or_else(|| proptest::test_runner::TestCaseError::fail("no coordinates"))?;
+            // compare the distance in meters from the lower left corner since
+            // a position close to the region border can be truncated to a
+            // pixel in the neighbouring region
+            let lower_left_corner = map_tile.lower_left_corner();
+            let meters_x = |grid_coordinates: &GridCoordinates, region_coordinates: &RegionCoordinates| {
+                f32::from(grid_coordinates.x() - lower_left_corner.x()) * 256f32
+                    + region_coordinates.x()
+            };
+            let meters_y = |grid_coordinates: &GridCoordinates, region_coordinates: &RegionCoordinates| {
+                f32::from(grid_coordinates.y() - lower_left_corner.y()) * 256f32
+                    + region_coordinates.y()
+            };
+            let meters_per_pixel = 1f32 / zoom_level.pixels_per_meter();
+            let difference_x = meters_x(&grid_coordinates, &region_coordinates)
+                - meters_x(&result_grid_coordinates, &result_region_coordinates);
+            let difference_y = meters_y(&grid_coordinates, &region_coordinates)
+                - meters_y(&result_grid_coordinates, &result_region_coordinates);
+            proptest::prop_assert!((-0.01f32..meters_per_pixel + 0.01f32).contains(&difference_x));
+            proptest::prop_assert!((-0.01f32..meters_per_pixel + 0.01f32).contains(&difference_y));
+        }
+    }
 }
@@ -1,5 +1,10 @@
 //! Contains functionality related to converting region names to grid coordinates and vice versa
-use sl_types::map::{GridCoordinates, GridRectangle, RegionName, RegionNameError, USBNotecard};
+use sl_types::map::{
+    GridCoordinates, GridCoordinatesExt as _, GridRectangle, Location, MapTileDescriptor,
+    RegionName, RegionNameError, USBNotecard, USBWaypoint, ZoomLevel,
+};
+
+use crate::grid_profile::GridProfile;
 
 /// Represents the possible errors that can occur when converting a region name to grid coordinates
 #[derive(Debug, thiserror::Error)]
@@ -27,6 +32,15 @@ pub enum RegionNameToGridCoordinatesError {
     Y(String, std::num::ParseIntError),
 }
 
+/// waits until the rate limiter (if any) allows another request
+async fn wait_for_ratelimiter(ratelimiter: Option<&ratelimit::Ratelimiter>) {
+    if let Some(ratelimiter) = ratelimiter {
+        while let Err(duration) = ratelimiter.try_wait() {
+            tokio::time::sleep(duration).await;
+        }
+    }
+}
+
 /// converts a `RegionName` to `GridCoordinates` using the Linden Lab API
 ///
 /// # Errors
@@ -43,12 +57,74 @@ pub async fn region_name_to_grid_coordinates(
 ) -> Result<
     (Option<GridCoordinates>, http_cache_semantics::CachePolicy),
     RegionNameToGridCoordinatesError,
+> {
+    region_name_to_grid_coordinates_with_grid_profile(
+        client,
+        &GridProfile::second_life_main(),
+        region_name,
+        cached_value_with_cache_policy,
+    )
+    .await
+}
+
+/// converts a `RegionName` to `GridCoordinates` using the equivalent of the
+/// Linden Lab API on the grid described by the `GridProfile`
+///
+/// # Errors
+///
+/// returns an error if the HTTP request fails or if the result couldn't
+/// be parsed properly
+pub async fn region_name_to_grid_coordinates_with_grid_profile(
+    client: &reqwest::Client,
+    grid_profile: &GridProfile,
+    region_name: &RegionName,
+    cached_value_with_cache_policy: Option<(
+        Option<GridCoordinates>,
+        http_cache_semantics::CachePolicy,
+    )>,
+) -> Result<
+    (Option<GridCoordinates>, http_cache_semantics::CachePolicy),
+    RegionNameToGridCoordinatesError,
+> {
+    region_name_to_grid_coordinates_ratelimited(
+        client,
+        grid_profile,
+        None,
+        region_name,
+        cached_value_with_cache_policy,
+    )
+    .await
+}
+
+/// converts a `RegionName` to `GridCoordinates` waiting for the rate
+/// limiter (if any) before making a request
+///
+/// # Errors
+///
+/// returns an error if the HTTP request fails or if the result couldn't
+/// be parsed properly
+async fn region_name_to_grid_coordinates_ratelimited(
+    client: &reqwest::Client,
+    grid_profile: &GridProfile,
+    ratelimiter: Option<&ratelimit::Ratelimiter>,
+    region_name: &RegionName,
+    cached_value_with_cache_policy: Option<(
+        Option<GridCoordinates>,
+        http_cache_semantics::CachePolicy,
+    )>,
+) -> Result<
+    (Option<GridCoordinates>, http_cache_semantics::CachePolicy),
+    RegionNameToGridCoordinatesError,
 > {
     tracing::debug!(
         "Looking up grid coordinates for region name {}",
         region_name
     );
-    let url = format!("https://cap.secondlife.com/cap/0/d661249b-2b5a-4436-966a-3d3b8d7a574f?var=coords&sim_name={}", region_name.to_string().replace(" ", "%20"));
+    let url = format!(
+        "{}?var=coords&sim_name={}",
+        grid_profile.map_block_query_url(),
+        region_name.to_url_component()
+    );
     let request = client.get(&url).build()?;
     if let Some((cached_value, cache_policy)) = cached_value_with_cache_policy {
         let now = std::time::SystemTime::now();
@@ -59,6 +135,7 @@ pub async fn region_name_to_grid_coordinates(
             return Ok((cached_value, cache_policy));
         }
     }
+    wait_for_ratelimiter(ratelimiter).await;
     let response = client
         .execute(
             request
@@ -130,12 +207,65 @@ pub async fn grid_coordinates_to_region_name(
     grid_coordinates: &GridCoordinates,
     cached_value_with_cache_policy: Option<(Option<RegionName>, http_cache_semantics::CachePolicy)>,
 ) -> Result<(Option<RegionName>, http_cache_semantics::CachePolicy), GridCoordinatesToRegionNameError>
+{
+    grid_coordinates_to_region_name_with_grid_profile(
+        client,
+        &GridProfile::second_life_main(),
+        grid_coordinates,
+        cached_value_with_cache_policy,
+    )
+    .await
+}
+
+/// converts `GridCoordinates` to a `RegionName` using the equivalent of the
+/// Linden Lab API on the grid described by the `GridProfile`
+///
+/// # Errors
+///
+/// returns an error if the HTTP request fails or if the result couldn't
+/// be parsed properly
+pub async fn grid_coordinates_to_region_name_with_grid_profile(
+    client: &reqwest::Client,
+    grid_profile: &GridProfile,
+    grid_coordinates: &GridCoordinates,
+    cached_value_with_cache_policy: Option<(Option<RegionName>, http_cache_semantics::CachePolicy)>,
+) -> Result<(Option<RegionName>, http_cache_semantics::CachePolicy), GridCoordinatesToRegionNameError>
+{
+    grid_coordinates_to_region_name_ratelimited(
+        client,
+        grid_profile,
+        None,
+        grid_coordinates,
+        cached_value_with_cache_policy,
+    )
+    .await
+}
+
+/// converts `GridCoordinates` to a `RegionName` waiting for the rate
+/// limiter (if any) before making a request
+///
+/// # Errors
+///
+/// returns an error if the HTTP request fails or if the result couldn't
+/// be parsed properly
+async fn grid_coordinates_to_region_name_ratelimited(
+    client: &reqwest::Client,
+    grid_profile: &GridProfile,
+    ratelimiter: Option<&ratelimit::Ratelimiter>,
+    grid_coordinates: &GridCoordinates,
+    cached_value_with_cache_policy: Option<(Option<RegionName>, http_cache_semantics::CachePolicy)>,
+) -> Result<(Option<RegionName>, http_cache_semantics::CachePolicy), GridCoordinatesToRegionNameError>
 {
     tracing::debug!(
         "Looking up region name for grid coordinates {:?}",
         grid_coordinates
     );
-    let url = format!("https://cap.secondlife.com/cap/0/b713fe80-283b-4585-af4d-a3b7d9a32492?var=region&grid_x={}&grid_y={}", grid_coordinates.x(), grid_coordinates.y());
+    let url = format!(
+        "{}?var=region&grid_x={}&grid_y={}",
+        grid_profile.name_service_url(),
+        grid_coordinates.x(),
+        grid_coordinates.y()
+    );
     let request = client.get(&url).build()?;
     if let Some((cached_value, cache_policy)) = cached_value_with_cache_policy {
         let now = std::time::SystemTime::now();
@@ -146,6 +276,7 @@ pub async fn grid_coordinates_to_region_name(
             return Ok((cached_value, cache_policy));
         }
     }
+    wait_for_ratelimiter(ratelimiter).await;
     let response = client
         .execute(
             request
@@ -175,12 +306,263 @@ pub async fn grid_coordinates_to_region_name(
     Ok((Some(region_name), cache_policy))
 }
 
+/// a backend used by the `RegionNameToGridCoordinatesCache` to look up
+/// region names and grid coordinates that are not cached locally
+///
+/// the cached value and its cache policy (if any) are passed in so the
+/// backend can decide if it is still fresh or needs to be looked up again
+///
+/// the returned futures are `Send` so lookups via a cache using any backend
+/// can be spawned on a multi-threaded runtime, implementations can still
+/// use `async fn`
+pub trait RegionLookupBackend: std::fmt::Debug + Send + Sync {
+    /// converts a `RegionName` to `GridCoordinates`
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the lookup fails
+    fn region_name_to_grid_coordinates(
+        &self,
+        region_name: &RegionName,
+        cached_value_with_cache_policy: Option<(
+            Option<GridCoordinates>,
+            http_cache_semantics::CachePolicy,
+        )>,
+    ) -> impl std::future::Future<
+        Output = Result<
+            (Option<GridCoordinates>, http_cache_semantics::CachePolicy),
+            RegionNameToGridCoordinatesError,
+        >,
+    > + Send;
+
+    /// converts `GridCoordinates` to a `RegionName`
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the lookup fails
+    fn grid_coordinates_to_region_name(
+        &self,
+        grid_coordinates: &GridCoordinates,
+        cached_value_with_cache_policy: Option<(
+            Option<RegionName>,
+            http_cache_semantics::CachePolicy,
+        )>,
+    ) -> impl std::future::Future<
+        Output = Result<
+            (Option<RegionName>, http_cache_semantics::CachePolicy),
+            GridCoordinatesToRegionNameError,
+        >,
+    > + Send;
+}
+
+/// the default `RegionLookupBackend` which queries the map API of the grid
+/// described by the `GridProfile` via HTTP
+///
+/// region names are looked up with
+/// `<map_block_query_url>?var=coords&sim_name=<region name>` and grid
+/// coordinates with `<name_service_url>?var=region&grid_x=<x>&grid_y=<y>`,
+/// see `region_name_to_grid_coordinates_with_grid_profile` and
+/// `grid_coordinates_to_region_name_with_grid_profile`
+#[derive(derive_more::Debug, Clone)]
+pub struct HttpRegionLookupBackend {
+    /// the reqwest Client used to lookup data not cached locally
+    client: reqwest::Client,
+    /// the URLs of the services of the grid used to lookup data not
+    /// cached locally
+    grid_profile: GridProfile,
+    /// the rate limiter for requests to the server
+    #[debug(skip)]
+    ratelimiter: Option<std::sync::Arc<ratelimit::Ratelimiter>>,
+}
+
+impl HttpRegionLookupBackend {
+    /// create a new backend using the given client and grid
+    #[must_use]
+    pub fn new(client: reqwest::Client, grid_profile: GridProfile) -> Self {
+        Self {
+            client,
+            grid_profile,
+            ratelimiter: None,
+        }
+    }
+
+    /// sets a rate limiter which is waited on before every request to the
+    /// server, lookups answered from fresh cached values are not limited
+    #[must_use]
+    pub fn with_ratelimiter(mut self, ratelimiter: ratelimit::Ratelimiter) -> Self {
+        self.ratelimiter = Some(std::sync::Arc::new(ratelimiter));
+        self
+    }
+}
+
+impl Default for HttpRegionLookupBackend {
+    fn default() -> Self {
+        Self::new(reqwest::Client::new(), GridProfile::second_life_main())
+    }
+}
+
+impl RegionLookupBackend for HttpRegionLookupBackend {
+    async fn region_name_to_grid_coordinates(
+        &self,
+        region_name: &RegionName,
+        cached_value_with_cache_policy: Option<(
+            Option<GridCoordinates>,
+            http_cache_semantics::CachePolicy,
+        )>,
+    ) -> Result<
+        (Option<GridCoordinates>, http_cache_semantics::CachePolicy),
+        RegionNameToGridCoordinatesError,
+    > {
+        region_name_to_grid_coordinates_ratelimited(
+            &self.client,
+            &self.grid_profile,
+            self.ratelimiter.as_deref(),
+            region_name,
+            cached_value_with_cache_policy,
+        )
+        .await
+    }
+
+    async fn grid_coordinates_to_region_name(
+        &self,
+        grid_coordinates: &GridCoordinates,
+        cached_value_with_cache_policy: Option<(
+            Option<RegionName>,
+            http_cache_semantics::CachePolicy,
+        )>,
+    ) -> Result<
+        (Option<RegionName>, http_cache_semantics::CachePolicy),
+        GridCoordinatesToRegionNameError,
+    > {
+        grid_coordinates_to_region_name_ratelimited(
+            &self.client,
+            &self.grid_profile,
+            self.ratelimiter.as_deref(),
+            grid_coordinates,
+            cached_value_with_cache_policy,
+        )
+        .await
+    }
+}
+
+/// a `RegionLookupBackend` that answers lookups from a fixed set of regions
+/// without any network access, e.g. for tests or offline use
+///
+/// all answers are considered fresh for an hour
+#[derive(Debug, Default)]
+pub struct StaticRegionLookupBackend {
+    /// the regions known to this backend
+    regions: std::collections::HashMap<RegionName, GridCoordinates>,
+    /// the number of lookups that were not answered from the cached value
+    lookup_count: std::sync::atomic::AtomicUsize,
+}
+
+impl StaticRegionLookupBackend {
+    /// create a new backend without any regions
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a region to the backend
+    #[must_use]
+    pub fn with_region(
+        mut self,
+        region_name: RegionName,
+        grid_coordinates: GridCoordinates,
+    ) -> Self {
+        self.regions.insert(region_name, grid_coordinates);
+        self
+    }
+
+    /// the number of lookups that were not answered from the cached value
+    /// passed in by the cache
+    #[must_use]
+    pub fn lookup_count(&self) -> usize {
+        self.lookup_count.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// returns the cached value if it is still fresh, otherwise counts the
+    /// lookup and returns None
+    fn fresh_cached_value<T>(
+        &self,
+        cached_value_with_cache_policy: Option<(T, http_cache_semantics::CachePolicy)>,
+    ) -> Option<(T, http_cache_semantics::CachePolicy)> {
+        if let Some((cached_value, cache_policy)) = cached_value_with_cache_policy {
+            if let http_cache_semantics::BeforeRequest::Fresh(_) =
+                cache_policy.before_request(&http::Request::new(()), std::time::SystemTime::now())
+            {
+                return Some((cached_value, cache_policy));
+            }
+        }
+        self.lookup_count
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        None
+    }
+
+    /// a cache policy that keeps answers fresh for an hour
+    fn cache_policy() -> http_cache_semantics::CachePolicy {
+        let mut response = http::Response::new(());
+        response.headers_mut().insert(
+            http::header::CACHE_CONTROL,
+            http::HeaderValue::from_static("max-age=3600"),
+        );
+        http_cache_semantics::CachePolicy::new(&http::Request::new(()), &response)
+    }
+}
+
+impl RegionLookupBackend for StaticRegionLookupBackend {
+    async fn region_name_to_grid_coordinates(
+        &self,
+        region_name: &RegionName,
+        cached_value_with_cache_policy: Option<(
+            Option<GridCoordinates>,
+            http_cache_semantics::CachePolicy,
+        )>,
+    ) -> Result<
+        (Option<GridCoordinates>, http_cache_semantics::CachePolicy),
+        RegionNameToGridCoordinatesError,
+    > {
+        if let Some(cached) = self.fresh_cached_value(cached_value_with_cache_policy) {
+            return Ok(cached);
+        }
+        Ok((self.regions.get(region_name).copied(), Self::cache_policy()))
+    }
+
+    async fn grid_coordinates_to_region_name(
+        &self,
+        grid_coordinates: &GridCoordinates,
+        cached_value_with_cache_policy: Option<(
+            Option<RegionName>,
+            http_cache_semantics::CachePolicy,
+        )>,
+    ) -> Result<
+        (Option<RegionName>, http_cache_semantics::CachePolicy),
+        GridCoordinatesToRegionNameError,
+    > {
+        if let Some(cached) = self.fresh_cached_value(cached_value_with_cache_policy) {
+            return Ok(cached);
+        }
+        Ok((
+            self.regions
+                .iter()
+                .find(|(_, region_grid_coordinates)| *region_grid_coordinates == grid_coordinates)
+                .map(|(region_name, _)| region_name.to_owned()),
+            Self::cache_policy(),
+        ))
+    }
+}
+
+/// the default number of concurrent lookups used when resolving many
+/// region names at once
+pub const DEFAULT_MAX_CONCURRENT_REGION_LOOKUPS: usize = 4;
+
 /// a cache for region names to grid coordinates
 /// that allows lookups in both directions
 #[derive(Debug)]
-pub struct RegionNameToGridCoordinatesCache {
-    /// the reqwest Client used to lookup data not cached locally
-    client: reqwest::Client,
+pub struct RegionNameToGridCoordinatesCache<B = HttpRegionLookupBackend> {
+    /// the backend used to lookup data not cached locally
+    backend: B,
     /// the cache database
     db: redb::Database,
     /// the in memory cache of region names to grid coordinates
@@ -189,6 +571,9 @@ pub struct RegionNameToGridCoordinatesCache {
     /// the in memory cache of grid coordinates to region names
     region_name_cache:
         lru::LruCache<GridCoordinates, (Option<RegionName>, http_cache_semantics::CachePolicy)>,
+    /// minimum time for which the absence of a region with a given name is
+    /// considered fresh regardless of the cache headers of the response
+    negative_min_fresh: Option<std::time::Duration>,
 }
 
 /// describes an error that can occur as part of the cache operation for the `RegionNameToGridCoordinatesCache`
@@ -251,18 +636,76 @@ impl RegionNameToGridCoordinatesCache {
     ///
     /// returns an error if the database could not be created or opened
     pub fn new(cache_directory: std::path::PathBuf) -> Result<Self, CacheError> {
-        let client = reqwest::Client::new();
+        Self::with_backend(cache_directory, HttpRegionLookupBackend::default())
+    }
+
+    /// sets the `GridProfile` of the grid to look up region names and
+    /// grid coordinates in
+    ///
+    /// since the cache database does not record the grid each grid should
+    /// use its own cache directory
+    ///
+    /// this is only available for caches using the default
+    /// `HttpRegionLookupBackend`, other backends have to be configured
+    /// before they are passed to `RegionNameToGridCoordinatesCache::with_backend`
+    #[must_use]
+    pub fn with_grid_profile(mut self, grid_profile: GridProfile) -> Self {
+        self.backend.grid_profile = grid_profile;
+        self
+    }
+
+    /// sets a rate limiter for the requests to the server, see
+    /// `HttpRegionLookupBackend::with_ratelimiter`
+    ///
+    /// this is only available for caches using the default
+    /// `HttpRegionLookupBackend`
+    #[must_use]
+    pub fn with_ratelimiter(mut self, ratelimiter: ratelimit::Ratelimiter) -> Self {
+        self.backend = self.backend.with_ratelimiter(ratelimiter);
+        self
+    }
+}
+
+impl<B: RegionLookupBackend> RegionNameToGridCoordinatesCache<B> {
+    /// create a new cache which uses the given backend to look up data
+    /// not cached locally
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the database could not be created or opened
+    pub fn with_backend(
+        cache_directory: std::path::PathBuf,
+        backend: B,
+    ) -> Result<Self, CacheError> {
         let db = redb::Database::create(cache_directory.join("region_name.redb"))?;
         let grid_coordinate_cache = lru::LruCache::unbounded();
         let region_name_cache = lru::LruCache::unbounded();
         Ok(Self {
-            client,
+            backend,
             db,
             grid_coordinate_cache,
             region_name_cache,
+            negative_min_fresh: None,
         })
     }
 
+    /// sets a minimum time for which the absence of a region with a given
+    /// name is considered fresh, even if the response did not allow caching
+    /// it at all or only for a shorter time
+    ///
+    /// this avoids repeated lookups for misspelled or removed region names
+    #[must_use]
+    pub fn with_negative_min_fresh(mut self, negative_min_fresh: std::time::Duration) -> Self {
+        self.negative_min_fresh = Some(negative_min_fresh);
+        self
+    }
+
+    /// the backend used to look up data not cached locally
+    #[must_use]
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
     /// get the grid coordinates for a region name
     ///
     /// # Errors
@@ -273,54 +716,136 @@ impl RegionNameToGridCoordinatesCache {
         region_name: &RegionName,
     ) -> Result<Option<GridCoordinates>, CacheError> {
         tracing::debug!("Retrieving grid coordinates for region {region_name:?}");
-        let cached_value_with_cache_policy = {
-            if let Some(memory_cached_value) = self.grid_coordinate_cache.get(region_name) {
-                Some(memory_cached_value.to_owned())
+        let cached_value_with_cache_policy = self.cached_grid_coordinates(region_name)?;
+        if self.is_negative_min_fresh(&cached_value_with_cache_policy) {
+            tracing::debug!("Using cached absence of region within minimum freshness");
+            return Ok(None);
+        }
+        let lookup_result = self
+            .backend
+            .region_name_to_grid_coordinates(region_name, cached_value_with_cache_policy)
+            .await;
+        self.store_grid_coordinates(region_name, lookup_result)
+    }
+
+    /// get the grid coordinates for many region names at once
+    ///
+    /// each distinct region name is only looked up once and region names
+    /// that are not freshly cached are looked up concurrently with at most
+    /// `max_concurrent_lookups` lookups in flight at the same time
+    ///
+    /// # Errors
+    ///
+    /// returns an error if either the local database operations or the HTTP requests fail
+    pub async fn get_grid_coordinates_batch(
+        &mut self,
+        region_names: &[RegionName],
+        max_concurrent_lookups: usize,
+    ) -> Result<std::collections::HashMap<RegionName, Option<GridCoordinates>>, CacheError> {
+        use futures_util::StreamExt as _;
+        let mut result = std::collections::HashMap::new();
+        let mut seen_region_names = std::collections::HashSet::new();
+        let mut pending_lookups = Vec::new();
+        for region_name in region_names {
+            if !seen_region_names.insert(region_name) {
+                continue;
+            }
+            let cached_value_with_cache_policy = self.cached_grid_coordinates(region_name)?;
+            if self.is_negative_min_fresh(&cached_value_with_cache_policy) {
+                result.insert(region_name.to_owned(), None);
+                continue;
+            }
+            pending_lookups.push((region_name.to_owned(), cached_value_with_cache_policy));
+        }
+        let backend = &self.backend;
+        let lookup_results = futures_util::stream::iter(pending_lookups)
+            .map(|(region_name, cached_value_with_cache_policy)| async move {
+                let lookup_result = backend
+                    .region_name_to_grid_coordinates(&region_name, cached_value_with_cache_policy)
+                    .await;
+                (region_name, lookup_result)
+            })
+            .buffer_unordered(max_concurrent_lookups.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        for (region_name, lookup_result) in lookup_results {
+            let grid_coordinates = self.store_grid_coordinates(&region_name, lookup_result)?;
+            result.insert(region_name, grid_coordinates);
+        }
+        Ok(result)
+    }
+
+    /// the cached grid coordinates (or absence of a region) for a region
+    /// name and their cache policy from the in-memory cache or the cache
+    /// database
+    fn cached_grid_coordinates(
+        &mut self,
+        region_name: &RegionName,
+    ) -> Result<Option<(Option<GridCoordinates>, http_cache_semantics::CachePolicy)>, CacheError>
+    {
+        if let Some(memory_cached_value) = self.grid_coordinate_cache.get(region_name) {
+            return Ok(Some(memory_cached_value.to_owned()));
+        }
+        let read_txn = self.db.begin_read()?;
+        let cache_policy = {
+            if let Ok(table) = read_txn.open_table(GRID_COORDINATE_CACHE_POLICY_TABLE) {
+                if let Some(access_guard) = table.get(region_name.to_owned().into_inner())? {
+                    let cache_policy: http_cache_semantics::CachePolicy =
+                        serde_json::from_str(&access_guard.value())?;
+                    Some(cache_policy)
+                } else {
+                    None
+                }
             } else {
-                let read_txn = self.db.begin_read()?;
-                let cache_policy = {
-                    if let Ok(table) = read_txn.open_table(GRID_COORDINATE_CACHE_POLICY_TABLE) {
-                        if let Some(access_guard) =
-                            table.get(region_name.to_owned().into_inner())?
-                        {
-                            let cache_policy: http_cache_semantics::CachePolicy =
-                                serde_json::from_str(&access_guard.value())?;
-                            Some(cache_policy)
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                };
-                if let Some(cache_policy) = cache_policy {
-                    let cached_value = {
-                        if let Ok(table) = read_txn.open_table(GRID_COORDINATE_CACHE_TABLE) {
-                            if let Some(access_guard) =
-                                table.get(region_name.to_owned().into_inner())?
-                            {
-                                let (x, y) = access_guard.value();
-                                Some(GridCoordinates::new(x, y))
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    };
-                    Some((cached_value, cache_policy))
+                None
+            }
+        };
+        let Some(cache_policy) = cache_policy else {
+            return Ok(None);
+        };
+        let cached_value = {
+            if let Ok(table) = read_txn.open_table(GRID_COORDINATE_CACHE_TABLE) {
+                if let Some(access_guard) = table.get(region_name.to_owned().into_inner())? {
+                    let (x, y) = access_guard.value();
+                    Some(GridCoordinates::new(x, y))
                 } else {
                     None
                 }
+            } else {
+                None
             }
         };
-        match region_name_to_grid_coordinates(
-            &self.client,
-            region_name,
-            cached_value_with_cache_policy,
-        )
-        .await
-        {
+        Ok(Some((cached_value, cache_policy)))
+    }
+
+    /// checks if a cached absence of a region is still within the minimum
+    /// freshness for negative responses
+    fn is_negative_min_fresh(
+        &self,
+        cached_value_with_cache_policy: &Option<(
+            Option<GridCoordinates>,
+            http_cache_semantics::CachePolicy,
+        )>,
+    ) -> bool {
+        let Some((None, cache_policy)) = cached_value_with_cache_policy else {
+            return false;
+        };
+        self.negative_min_fresh.is_some_and(|negative_min_fresh| {
+            cache_policy.age(std::time::SystemTime::now()) < negative_min_fresh
+        })
+    }
+
+    /// stores the result of looking up the grid coordinates of a region
+    /// name in the caches if the cache policy allows it
+    fn store_grid_coordinates(
+        &mut self,
+        region_name: &RegionName,
+        lookup_result: Result<
+            (Option<GridCoordinates>, http_cache_semantics::CachePolicy),
+            RegionNameToGridCoordinatesError,
+        >,
+    ) -> Result<Option<GridCoordinates>, CacheError> {
+        match lookup_result {
             Ok((Some(grid_coordinates), cache_policy)) => {
                 if cache_policy.is_storable() {
                     tracing::debug!("Storing grid coordinates in cache");
@@ -362,7 +887,7 @@ impl RegionNameToGridCoordinatesCache {
                 Ok(Some(grid_coordinates))
             }
             Ok((None, cache_policy)) => {
-                if cache_policy.is_storable() {
+                if cache_policy.is_storable() || self.negative_min_fresh.is_some() {
                     tracing::debug!("Storing negative response in cache");
                     let write_txn = self.db.begin_write()?;
                     {
@@ -451,12 +976,10 @@ impl RegionNameToGridCoordinatesCache {
                 }
             }
         };
-        match grid_coordinates_to_region_name(
-            &self.client,
-            grid_coordinates,
-            cached_value_with_cache_policy,
-        )
-        .await
+        match self
+            .backend
+            .grid_coordinates_to_region_name(grid_coordinates, cached_value_with_cache_policy)
+            .await
         {
             Ok((Some(region_name), cache_policy)) => {
                 if cache_policy.is_storable() {
@@ -559,8 +1082,8 @@ pub enum USBNotecardToGridRectangleError {
 /// # Errors
 ///
 /// returns an error if there were no waypoints or if conversions to grid coordinates failed
-pub async fn usb_notecard_to_grid_rectangle(
-    region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache,
+pub async fn usb_notecard_to_grid_rectangle<B: RegionLookupBackend>(
+    region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
     usb_notecard: &USBNotecard,
 ) -> Result<GridRectangle, USBNotecardToGridRectangleError> {
     let mut lower_left_x = None;
@@ -616,6 +1139,102 @@ pub async fn usb_notecard_to_grid_rectangle(
     ))
 }
 
+/// resolves a region name to the descriptor of the map tile containing that
+/// region at the given zoom level
+///
+/// returns None if the region does not exist (anymore)
+///
+/// # Errors
+///
+/// returns an error if converting the region name to grid coordinates fails
+pub async fn region_tile_descriptor<B: RegionLookupBackend>(
+    region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+    zoom_level: ZoomLevel,
+    region_name: &RegionName,
+) -> Result<Option<MapTileDescriptor>, CacheError> {
+    Ok(region_name_to_grid_coordinates_cache
+        .get_grid_coordinates(region_name)
+        .await?
+        .map(|grid_coordinates| MapTileDescriptor::new(zoom_level, grid_coordinates)))
+}
+
+/// A trait to allow adding methods to collections of `Location`s that
+/// need to resolve region names
+pub trait LocationsExt {
+    /// returns the `GridRectangle` containing the regions of all the
+    /// locations in this container
+    ///
+    /// locations in regions that do not exist (anymore) are ignored
+    ///
+    /// returns None if the container is empty or none of the regions exist
+    ///
+    /// # Errors
+    ///
+    /// returns an error if converting the region names to grid coordinates
+    /// fails
+    #[allow(async_fn_in_trait)]
+    async fn bounding_grid_rectangle<B: RegionLookupBackend>(
+        &self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+    ) -> Result<Option<GridRectangle>, CacheError>;
+}
+
+impl LocationsExt for [Location] {
+    async fn bounding_grid_rectangle<B: RegionLookupBackend>(
+        &self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+    ) -> Result<Option<GridRectangle>, CacheError> {
+        let mut grid_coordinates = Vec::new();
+        for location in self {
+            if let Some(region_grid_coordinates) = region_name_to_grid_coordinates_cache
+                .get_grid_coordinates(location.region_name())
+                .await?
+            {
+                grid_coordinates.push(region_grid_coordinates);
+            } else {
+                tracing::warn!(
+                    "Ignoring location {location} in bounding rectangle calculation, region does not exist"
+                );
+            }
+        }
+        Ok(grid_coordinates.bounding_rectangle())
+    }
+}
+
+/// A trait to allow adding methods to `USBNotecard` that need to resolve
+/// region names
+pub trait USBNotecardExt {
+    /// checks for each waypoint whether its region still exists, regions
+    /// do get removed from the grid so old routes can contain dead waypoints
+    ///
+    /// # Errors
+    ///
+    /// returns an error if converting the region names to grid coordinates
+    /// fails
+    #[allow(async_fn_in_trait)]
+    async fn validate_regions<B: RegionLookupBackend>(
+        &self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+    ) -> Result<Vec<(USBWaypoint, bool)>, CacheError>;
+}
+
+impl USBNotecardExt for USBNotecard {
+    async fn validate_regions<B: RegionLookupBackend>(
+        &self,
+        region_name_to_grid_coordinates_cache: &mut RegionNameToGridCoordinatesCache<B>,
+    ) -> Result<Vec<(USBWaypoint, bool)>, CacheError> {
+        let mut result = Vec::new();
+        for waypoint in self.waypoints() {
+            let exists = region_name_to_grid_coordinates_cache
+                .get_grid_coordinates(waypoint.location().region_name())
+                .await?
+                .is_some();
+            result.push((waypoint.to_owned(), exists));
+        }
+        Ok(result)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -752,4 +1371,328 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_cache_with_custom_grid_profile() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let mut server = mockito::Server::new_async().await;
+        let map_block_query_mock = server
+            .mock("GET", "/map-block-query")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("var".into(), "coords".into()),
+                mockito::Matcher::UrlEncoded("sim_name".into(), "Test Region".into()),
+            ]))
+            .with_status(200)
+            .with_header("cache-control", "max-age=3600")
+            .with_body("var coords = {'x' : 1000, 'y' : 1001 };")
+            .expect(1)
+            .create_async()
+            .await;
+        let name_service_mock = server
+            .mock("GET", "/name-service")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("var".into(), "region".into()),
+                mockito::Matcher::UrlEncoded("grid_x".into(), "1000".into()),
+                mockito::Matcher::UrlEncoded("grid_y".into(), "1001".into()),
+            ]))
+            .with_status(200)
+            .with_header("cache-control", "max-age=3600")
+            .with_body("var region='Test Region';")
+            .expect(1)
+            .create_async()
+            .await;
+        let grid_profile = GridProfile::new(
+            server.url(),
+            format!("{}/map-block-query", server.url()),
+            format!("{}/name-service", server.url()),
+        );
+        let mut cache = RegionNameToGridCoordinatesCache::new(tempdir.path().to_path_buf())?
+            .with_grid_profile(grid_profile);
+        assert_eq!(
+            cache
+                .get_grid_coordinates(&RegionName::try_new("Test Region")?)
+                .await?,
+            Some(GridCoordinates::new(1000, 1001))
+        );
+        assert_eq!(
+            cache
+                .get_region_name(&GridCoordinates::new(1000, 1001))
+                .await?,
+            Some(RegionName::try_new("Test Region")?)
+        );
+        map_block_query_mock.assert_async().await;
+        name_service_mock.assert_async().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region_tile_descriptor() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let backend = StaticRegionLookupBackend::new().with_region(
+            RegionName::try_new("Da Boom")?,
+            GridCoordinates::new(1136, 1075),
+        );
+        let mut cache =
+            RegionNameToGridCoordinatesCache::with_backend(tempdir.path().to_path_buf(), backend)?;
+        let zoom_level = ZoomLevel::try_new(2)?;
+        assert_eq!(
+            region_tile_descriptor(&mut cache, zoom_level, &RegionName::try_new("Da Boom")?)
+                .await?,
+            Some(MapTileDescriptor::new(
+                zoom_level,
+                GridCoordinates::new(1136, 1074)
+            ))
+        );
+        assert_eq!(
+            region_tile_descriptor(&mut cache, zoom_level, &RegionName::try_new("Gone Region")?)
+                .await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_usb_notecard_validate_regions() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let backend = StaticRegionLookupBackend::new().with_region(
+            RegionName::try_new("Region A")?,
+            GridCoordinates::new(1000, 1001),
+        );
+        let mut cache =
+            RegionNameToGridCoordinatesCache::with_backend(tempdir.path().to_path_buf(), backend)?;
+        let usb_notecard: USBNotecard = "Region%20A/10/20/30\nGone%20Region/128/128/0".parse()?;
+        let validated = usb_notecard.validate_regions(&mut cache).await?;
+        assert_eq!(
+            validated
+                .iter()
+                .map(|(waypoint, exists)| (waypoint.location().region_name().to_owned(), *exists))
+                .collect::<Vec<_>>(),
+            vec![
+                (RegionName::try_new("Region A")?, true),
+                (RegionName::try_new("Gone Region")?, false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_with_static_backend() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let backend = StaticRegionLookupBackend::new().with_region(
+            RegionName::try_new("Region A")?,
+            GridCoordinates::new(1000, 1001),
+        );
+        let mut cache =
+            RegionNameToGridCoordinatesCache::with_backend(tempdir.path().to_path_buf(), backend)?;
+        assert_eq!(
+            cache
+                .get_grid_coordinates(&RegionName::try_new("Region A")?)
+                .await?,
+            Some(GridCoordinates::new(1000, 1001))
+        );
+        assert_eq!(
+            cache
+                .get_grid_coordinates(&RegionName::try_new("Gone Region")?)
+                .await?,
+            None
+        );
+        assert_eq!(
+            cache
+                .get_region_name(&GridCoordinates::new(1000, 1001))
+                .await?,
+            Some(RegionName::try_new("Region A")?)
+        );
+        assert_eq!(cache.backend().lookup_count(), 3);
+        assert_eq!(
+            cache
+                .get_grid_coordinates(&RegionName::try_new("Region A")?)
+                .await?,
+            Some(GridCoordinates::new(1000, 1001))
+        );
+        assert_eq!(cache.backend().lookup_count(), 3);
+        Ok(())
+    }
+
+    /// resolves a region name in a spawned task, this only compiles if the
+    /// lookup futures are `Send` for any backend
+    async fn spawn_get_grid_coordinates<B: RegionLookupBackend + 'static>(
+        mut cache: RegionNameToGridCoordinatesCache<B>,
+        region_name: RegionName,
+    ) -> Result<Option<GridCoordinates>, Box<dyn std::error::Error>> {
+        Ok(tokio::spawn(async move { cache.get_grid_coordinates(&region_name).await }).await??)
+    }
+
+    #[tokio::test]
+    async fn test_cache_lookup_in_spawned_task() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let backend = StaticRegionLookupBackend::new().with_region(
+            RegionName::try_new("Region A")?,
+            GridCoordinates::new(1000, 1001),
+        );
+        let cache =
+            RegionNameToGridCoordinatesCache::with_backend(tempdir.path().to_path_buf(), backend)?;
+        assert_eq!(
+            spawn_get_grid_coordinates(cache, RegionName::try_new("Region A")?).await?,
+            Some(GridCoordinates::new(1000, 1001))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_negative_min_fresh() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/map-block-query")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "sim_name".into(),
+                "Gone Region".into(),
+            ))
+            .with_status(200)
+            .with_header("cache-control", "no-store")
+            .with_body("var coords = {'error' : true };")
+            .expect(1)
+            .create_async()
+            .await;
+        let grid_profile = GridProfile::new(
+            server.url(),
+            format!("{}/map-block-query", server.url()),
+            format!("{}/name-service", server.url()),
+        );
+        let mut cache = RegionNameToGridCoordinatesCache::new(tempdir.path().to_path_buf())?
+            .with_grid_profile(grid_profile)
+            .with_negative_min_fresh(std::time::Duration::from_secs(3600));
+        for _ in 0..2 {
+            assert_eq!(
+                cache
+                    .get_grid_coordinates(&RegionName::try_new("Gone Region")?)
+                    .await?,
+                None
+            );
+        }
+        mock.assert_async().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_get_grid_coordinates_batch() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let backend = StaticRegionLookupBackend::new()
+            .with_region(
+                RegionName::try_new("Region A")?,
+                GridCoordinates::new(1000, 1001),
+            )
+            .with_region(
+                RegionName::try_new("Region B")?,
+                GridCoordinates::new(1003, 999),
+            );
+        let mut cache =
+            RegionNameToGridCoordinatesCache::with_backend(tempdir.path().to_path_buf(), backend)?;
+        let region_names = vec![
+            RegionName::try_new("Region A")?,
+            RegionName::try_new("Region B")?,
+            RegionName::try_new("Region A")?,
+            RegionName::try_new("Gone Region")?,
+        ];
+        let resolved = cache
+            .get_grid_coordinates_batch(&region_names, DEFAULT_MAX_CONCURRENT_REGION_LOOKUPS)
+            .await?;
+        assert_eq!(resolved.len(), 3);
+        assert_eq!(
+            resolved.get(&RegionName::try_new("Region A")?),
+            Some(&Some(GridCoordinates::new(1000, 1001)))
+        );
+        assert_eq!(
+            resolved.get(&RegionName::try_new("Region B")?),
+            Some(&Some(GridCoordinates::new(1003, 999)))
+        );
+        assert_eq!(
+            resolved.get(&RegionName::try_new("Gone Region")?),
+            Some(&None)
+        );
+        assert_eq!(cache.backend().lookup_count(), 3);
+        let resolved_again = cache
+            .get_grid_coordinates_batch(&region_names, DEFAULT_MAX_CONCURRENT_REGION_LOOKUPS)
+            .await?;
+        assert_eq!(resolved_again, resolved);
+        assert_eq!(cache.backend().lookup_count(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_get_grid_coordinates_batch_respects_ratelimiter(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/map-block-query")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("cache-control", "max-age=3600")
+            .with_body("var coords = {'x' : 1000, 'y' : 1001 };")
+            .expect(4)
+            .create_async()
+            .await;
+        let grid_profile = GridProfile::new(
+            server.url(),
+            format!("{}/map-block-query", server.url()),
+            format!("{}/name-service", server.url()),
+        );
+        let ratelimiter = ratelimit::Ratelimiter::builder(1, std::time::Duration::from_millis(100))
+            .max_tokens(1)
+            .initial_available(1)
+            .build()?;
+        let mut cache = RegionNameToGridCoordinatesCache::new(tempdir.path().to_path_buf())?
+            .with_grid_profile(grid_profile)
+            .with_ratelimiter(ratelimiter);
+        let region_names = vec![
+            RegionName::try_new("Region A")?,
+            RegionName::try_new("Region B")?,
+            RegionName::try_new("Region C")?,
+            RegionName::try_new("Region D")?,
+        ];
+        let start = std::time::Instant::now();
+        cache
+            .get_grid_coordinates_batch(&region_names, DEFAULT_MAX_CONCURRENT_REGION_LOOKUPS)
+            .await?;
+        // all but the first lookup have to wait for the ratelimiter to refill
+        assert!(start.elapsed() >= std::time::Duration::from_millis(300));
+        mock.assert_async().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_locations_bounding_grid_rectangle() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let backend = StaticRegionLookupBackend::new()
+            .with_region(
+                RegionName::try_new("Region A")?,
+                GridCoordinates::new(1000, 1001),
+            )
+            .with_region(
+                RegionName::try_new("Region B")?,
+                GridCoordinates::new(1003, 999),
+            );
+        let mut cache =
+            RegionNameToGridCoordinatesCache::with_backend(tempdir.path().to_path_buf(), backend)?;
+        let locations: Vec<Location> = vec![
+            "Region%20A/10/20/30".parse()?,
+            "Gone%20Region/128/128/0".parse()?,
+            "Region%20B/200/100/30".parse()?,
+        ];
+        assert_eq!(
+            locations.bounding_grid_rectangle(&mut cache).await?,
+            Some(GridRectangle::new(
+                GridCoordinates::new(1000, 999),
+                GridCoordinates::new(1003, 1001),
+            ))
+        );
+        assert_eq!(
+            Vec::<Location>::new()
+                .bounding_grid_rectangle(&mut cache)
+                .await?,
+            None
+        );
+        Ok(())
+    }
 }
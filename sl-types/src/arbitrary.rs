@@ -0,0 +1,80 @@
+//! `proptest` strategies for the map types, respecting their validity
+//! constraints, for use in property tests and fuzzing
+
+use proptest::prelude::{any, Arbitrary, BoxedStrategy, Strategy as _};
+
+use crate::map::{
+    GridCoordinates, GridRectangle, Location, RegionCoordinates, RegionName, ZoomLevel,
+};
+
+impl Arbitrary for GridCoordinates {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (any::<u16>(), any::<u16>())
+            .prop_map(|(x, y)| GridCoordinates::new(x, y))
+            .boxed()
+    }
+}
+
+impl Arbitrary for GridRectangle {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (any::<GridCoordinates>(), any::<GridCoordinates>())
+            .prop_map(|(corner1, corner2)| GridRectangle::new(corner1, corner2))
+            .boxed()
+    }
+}
+
+/// region coordinates are inside the region horizontally and below the
+/// maximum build height vertically
+impl Arbitrary for RegionCoordinates {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (0f32..256f32, 0f32..256f32, 0f32..4096f32)
+            .prop_map(|(x, y, z)| RegionCoordinates::new(x, y, z))
+            .boxed()
+    }
+}
+
+/// region names start and end with a letter or digit and contain spaces and
+/// the allowed punctuation characters in between
+impl Arbitrary for RegionName {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        "[A-Za-z0-9][A-Za-z0-9 '._-]{1,33}[A-Za-z0-9]"
+            .prop_filter_map("invalid region name", |s| RegionName::try_new(s).ok())
+            .boxed()
+    }
+}
+
+impl Arbitrary for Location {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (any::<RegionName>(), any::<u8>(), any::<u8>(), any::<u16>())
+            .prop_map(|(region_name, x, y, z)| Location::new(region_name, x, y, z))
+            .boxed()
+    }
+}
+
+impl Arbitrary for ZoomLevel {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (1u8..=8)
+            .prop_filter_map("invalid zoom level", |zoom_level| {
+                ZoomLevel::try_new(zoom_level).ok()
+            })
+            .boxed()
+    }
+}
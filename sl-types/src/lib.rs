@@ -1,3 +1,6 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(any(test, feature = "proptest"))]
+mod arbitrary;
 pub mod map;
+pub mod prelude;
@@ -37,6 +37,39 @@ impl GridCoordinates {
     pub fn y(&self) -> u16 {
         self.y
     }
+
+    /// a cheap check if a region could plausibly exist at these grid
+    /// coordinates, i.e. if they are inside the area of the grid with
+    /// regions on the Second Life main grid (roughly x 395 to 1358 and
+    /// y 479 to 1430)
+    ///
+    /// this allows skipping network requests for clearly out of range
+    /// coordinates, it does not mean there is a region at these coordinates
+    ///
+    /// this is only valid for the Second Life main grid, other grids can
+    /// have regions anywhere
+    #[must_use]
+    pub fn is_plausible_region(&self) -> bool {
+        (395..=1358).contains(&self.x) && (479..=1430).contains(&self.y)
+    }
+
+    /// the `Location` at the center of the region at these grid coordinates
+    /// at ground level, e.g. for teleport links to the middle of a region
+    ///
+    /// the grid coordinates are not checked against the region name
+    #[must_use]
+    pub fn center_location(&self, region_name: RegionName) -> Location {
+        Location::new(region_name, 128, 128, 0)
+    }
+
+    /// the `Location` at the south west (lower left) corner of the region at
+    /// these grid coordinates at ground level
+    ///
+    /// the grid coordinates are not checked against the region name
+    #[must_use]
+    pub fn corner_location(&self, region_name: RegionName) -> Location {
+        Location::new(region_name, 0, 0, 0)
+    }
 }
 
 /// an offset between two `GridCoordinates`
@@ -370,8 +403,96 @@ impl RegionCoordinates {
     pub fn z(&self) -> f32 {
         self.z
     }
+
+    /// the coordinates rounded down to whole meters
+    ///
+    /// negative values and NaN are snapped to 0, values too large for
+    /// a `u16` to `u16::MAX`
+    #[must_use]
+    pub fn snapped_to_meter(&self) -> (u16, u16, u16) {
+        (
+            self.x.floor() as u16,
+            self.y.floor() as u16,
+            self.z.floor() as u16,
+        )
+    }
+}
+
+/// `RegionCoordinates` snapped to whole meters
+///
+/// unlike `RegionCoordinates` this can be used as a key in a `HashMap`
+/// or `BTreeMap`, e.g. to bucket object positions inside a region
+#[derive(
+    Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct RegionCoordinatesKey {
+    /// the x coordinate in whole meters
+    x: u16,
+    /// the y coordinate in whole meters
+    y: u16,
+    /// the z coordinate in whole meters
+    z: u16,
+}
+
+impl RegionCoordinatesKey {
+    /// The x coordinate in whole meters
+    #[must_use]
+    pub fn x(&self) -> u16 {
+        self.x
+    }
+
+    /// The y coordinate in whole meters
+    #[must_use]
+    pub fn y(&self) -> u16 {
+        self.y
+    }
+
+    /// The z coordinate in whole meters
+    #[must_use]
+    pub fn z(&self) -> u16 {
+        self.z
+    }
+}
+
+impl From<&RegionCoordinates> for RegionCoordinatesKey {
+    fn from(region_coordinates: &RegionCoordinates) -> Self {
+        let (x, y, z) = region_coordinates.snapped_to_meter();
+        Self { x, y, z }
+    }
 }
 
+impl From<RegionCoordinatesKey> for RegionCoordinates {
+    fn from(key: RegionCoordinatesKey) -> Self {
+        Self::new(f32::from(key.x), f32::from(key.y), f32::from(key.z))
+    }
+}
+
+/// checks if a (trimmed) string only consists of characters commonly used
+/// in region names
+///
+/// This is a conservative heuristic, not an official rule: ASCII letters,
+/// digits and spaces as well as a few punctuation characters (apostrophes,
+/// hyphens, periods and underscores), starting and ending with a letter or
+/// digit. It is only used by `RegionName::is_valid_candidate`, names
+/// returned by the grid or stored in existing notecards and caches are not
+/// checked against it
+fn is_valid_region_name(s: &str) -> bool {
+    let starts_and_ends_alphanumeric = s.chars().next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && s.chars()
+            .next_back()
+            .is_some_and(|c| c.is_ascii_alphanumeric());
+    starts_and_ends_alphanumeric
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '\'' | '-' | '.' | '_'))
+}
+
+/// the characters that need to be percent-encoded in a region name when it
+/// is used as a URL path component
+const REGION_NAME_URL_ENCODE_SET: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_');
+
 /// The name of a region
 #[nutype::nutype(
     sanitize(trim),
@@ -391,6 +512,42 @@ impl RegionCoordinates {
 )]
 pub struct RegionName(String);
 
+impl RegionName {
+    /// checks if the given string would be accepted as a `RegionName`
+    /// (after trimming surrounding whitespace) and only consists of the
+    /// characters commonly used in region names
+    ///
+    /// This allows rejecting unlikely region names early, e.g. user input
+    /// before sending it to any of the APIs that look up regions by name.
+    /// `RegionName::try_new` itself only checks the length so names
+    /// returned by the grid are never rejected
+    #[must_use]
+    pub fn is_valid_candidate(s: &str) -> bool {
+        RegionName::try_new(s).is_ok() && is_valid_region_name(s.trim())
+    }
+
+    /// creates a `RegionName` from a percent-encoded URL path component
+    /// as used in SLURLs and map URLs (e.g. `Beach%20Valley` or `O%27Hare`)
+    ///
+    /// This is the single place where region names are decoded from URLs,
+    /// all URL parsing code should use it to ensure consistent decoding
+    ///
+    /// # Errors
+    ///
+    /// returns an error if the decoded string is not a valid `RegionName`
+    pub fn try_from_url_component(s: &str) -> Result<Self, RegionNameError> {
+        RegionName::try_new(percent_encoding::percent_decode_str(s).decode_utf8_lossy())
+    }
+
+    /// returns the region name percent-encoded for use as a URL path
+    /// component, this is the inverse of `RegionName::try_from_url_component`
+    #[must_use]
+    pub fn to_url_component(&self) -> String {
+        percent_encoding::utf8_percent_encode(&self.to_string(), REGION_NAME_URL_ENCODE_SET)
+            .to_string()
+    }
+}
+
 /// A location inside Second Life the way it is usually represented in
 /// SLURLs or map URLs, based on a Region Name and integer coordinates
 /// inside the region
@@ -438,6 +595,22 @@ pub enum LocationParseError {
     Z(String, std::num::ParseIntError),
 }
 
+impl LocationParseError {
+    /// the reason the region name was rejected if this is an error parsing
+    /// the region name
+    ///
+    /// this allows distinguishing too short (including empty after trimming
+    /// whitespace) and too long region names without matching on the error
+    /// message
+    #[must_use]
+    pub fn region_name_error(&self) -> Option<&RegionNameError> {
+        match self {
+            LocationParseError::RegionName(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl std::str::FromStr for Location {
     type Err = LocationParseError;
 
@@ -446,7 +619,7 @@ impl std::str::FromStr for Location {
         let usb_parts = s.split(',').collect::<Vec<_>>();
         let parts = usb_parts[0].split('/').collect::<Vec<_>>();
         if parts.len() == 4 {
-            let region_name = RegionName::try_new(parts[0].replace("%20", " "))
+            let region_name = RegionName::try_from_url_component(parts[0])
                 .map_err(|err| LocationParseError::RegionName(s.to_owned(), err))?;
             let x = parts[1]
                 .parse()
@@ -489,7 +662,7 @@ impl std::str::FromStr for Location {
                     parts[3].to_owned(),
                 ));
             }
-            let region_name = RegionName::try_new(parts[4].replace("%20", " "))
+            let region_name = RegionName::try_from_url_component(parts[4])
                 .map_err(|err| LocationParseError::RegionName(s.to_owned(), err))?;
             let x = parts[5]
                 .parse()
@@ -555,7 +728,38 @@ impl Location {
     pub fn as_maps_url(&self) -> String {
         format!(
             "https://maps.secondlife.com/secondlife/{}/{}/{}/{}",
-            self.region_name, self.x, self.y, self.z
+            self.region_name.to_url_component(),
+            self.x,
+            self.y,
+            self.z
+        )
+    }
+
+    /// returns a slurl.com URL for the `Location`
+    #[must_use]
+    pub fn as_slurl(&self) -> String {
+        format!("http://slurl.com/secondlife/{self}")
+    }
+
+    /// returns a viewer URI that teleports to the `Location` when clicked
+    /// in the viewer
+    #[must_use]
+    pub fn as_app_teleport_uri(&self) -> String {
+        format!("secondlife:///app/teleport/{self}")
+    }
+}
+
+/// displays the `Location` in the bare `Region%20Name/x/y/z` form also used
+/// in USB notecards
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}/{}",
+            self.region_name.to_url_component(),
+            self.x,
+            self.y,
+            self.z
         )
     }
 }
@@ -715,6 +919,43 @@ impl ZoomLevel {
     }
 }
 
+/// the layers of the map served by the map server
+#[derive(
+    Debug, Clone, Copy, Default, Hash, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub enum MapLayer {
+    /// the regular map with terrain and objects
+    #[default]
+    Objects,
+    /// the terrain only without any objects
+    Terrain,
+    /// the land for sale overlay
+    LandForSale,
+}
+
+impl MapLayer {
+    /// all the map layers
+    pub const ALL: [MapLayer; 3] = [MapLayer::Objects, MapLayer::Terrain, MapLayer::LandForSale];
+
+    /// is this the default objects layer
+    #[must_use]
+    pub fn is_objects(&self) -> bool {
+        *self == MapLayer::Objects
+    }
+}
+
+/// formats the `MapLayer` the same way as the suffix of the map tile file names
+/// on the map server, e.g. `objects` or `land-for-sale`
+impl std::fmt::Display for MapLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapLayer::Objects => write!(f, "objects"),
+            MapLayer::Terrain => write!(f, "terrain"),
+            MapLayer::LandForSale => write!(f, "land-for-sale"),
+        }
+    }
+}
+
 /// describes a map tile
 #[derive(Debug, Clone, Hash, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct MapTileDescriptor {
@@ -722,6 +963,9 @@ pub struct MapTileDescriptor {
     zoom_level: ZoomLevel,
     /// the lower left corner of the map tile
     lower_left_corner: GridCoordinates,
+    /// the map layer of the map tile
+    #[serde(default, skip_serializing_if = "MapLayer::is_objects")]
+    layer: MapLayer,
 }
 
 impl MapTileDescriptor {
@@ -735,9 +979,22 @@ impl MapTileDescriptor {
         MapTileDescriptor {
             zoom_level,
             lower_left_corner,
+            layer: MapLayer::Objects,
         }
     }
 
+    /// the same map tile on a different map layer
+    #[must_use]
+    pub fn with_layer(self, layer: MapLayer) -> Self {
+        MapTileDescriptor { layer, ..self }
+    }
+
+    /// the `MapLayer` of the map tile
+    #[must_use]
+    pub fn layer(&self) -> MapLayer {
+        self.layer
+    }
+
     /// the `ZoomLevel` of the map tile
     #[must_use]
     pub fn zoom_level(&self) -> &ZoomLevel {
@@ -762,6 +1019,13 @@ impl MapTileDescriptor {
         self.zoom_level.tile_size_in_pixels()
     }
 
+    /// the file name of this map tile on the map server,
+    /// e.g. `map-1-1136-1075-objects.jpg`
+    #[must_use]
+    pub fn tile_filename(&self) -> String {
+        format!("{self}-{}.jpg", self.layer)
+    }
+
     /// the grid rectangle covered by this map tile
     #[must_use]
     pub fn grid_rectangle(&self) -> GridRectangle {
@@ -775,6 +1039,89 @@ impl MapTileDescriptor {
     }
 }
 
+/// formats the `MapTileDescriptor` the same way as the map tile file names
+/// on the map server (without the layer suffix), e.g. `map-1-1136-1075`
+impl std::fmt::Display for MapTileDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "map-{}-{}-{}",
+            self.zoom_level,
+            self.lower_left_corner.x(),
+            self.lower_left_corner.y()
+        )
+    }
+}
+
+/// the possible errors that can occur when parsing a String to a `MapTileDescriptor`
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MapTileDescriptorParseError {
+    /// the string did not have the expected map-{zoom}-{x}-{y} format
+    #[error("unexpected map tile descriptor format {0}, expected map-{{zoom}}-{{x}}-{{y}} optionally followed by a layer suffix like -objects.jpg")]
+    UnexpectedFormat(String),
+    /// error parsing the zoom level
+    #[error("error parsing the zoom level {0}: {1}")]
+    ZoomLevel(String, std::num::ParseIntError),
+    /// the zoom level is out of range
+    #[error("invalid zoom level {0}: {1}")]
+    InvalidZoomLevel(String, ZoomLevelError),
+    /// error parsing the X coordinate
+    #[error("error parsing the X coordinate {0}: {1}")]
+    X(String, std::num::ParseIntError),
+    /// error parsing the Y coordinate
+    #[error("error parsing the Y coordinate {0}: {1}")]
+    Y(String, std::num::ParseIntError),
+    /// the coordinates are not the lower left corner of a map tile
+    /// at the given zoom level
+    #[error(
+        "the coordinates in {0} are not the lower left corner of a map tile at that zoom level"
+    )]
+    NotLowerLeftCorner(String),
+}
+
+/// parses the map tile file names used by the map server and the map tile
+/// cache, with or without the layer suffix (e.g. `-objects.jpg`), without
+/// a suffix the `MapLayer::Objects` layer is assumed
+impl std::str::FromStr for MapTileDescriptor {
+    type Err = MapTileDescriptorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (without_suffix, layer) = MapLayer::ALL
+            .iter()
+            .find_map(|layer| {
+                s.strip_suffix(&format!("-{layer}.jpg"))
+                    .map(|without_suffix| (without_suffix, *layer))
+            })
+            .unwrap_or((s, MapLayer::Objects));
+        let Some(("map", rest)) = without_suffix.split_once('-') else {
+            return Err(MapTileDescriptorParseError::UnexpectedFormat(s.to_owned()));
+        };
+        let parts = rest.split('-').collect::<Vec<_>>();
+        let [zoom_level, x, y] = parts[..] else {
+            return Err(MapTileDescriptorParseError::UnexpectedFormat(s.to_owned()));
+        };
+        let zoom_level = zoom_level
+            .parse::<u8>()
+            .map_err(|err| MapTileDescriptorParseError::ZoomLevel(s.to_owned(), err))?;
+        let zoom_level = ZoomLevel::try_new(zoom_level)
+            .map_err(|err| MapTileDescriptorParseError::InvalidZoomLevel(s.to_owned(), err))?;
+        let x = x
+            .parse::<u16>()
+            .map_err(|err| MapTileDescriptorParseError::X(s.to_owned(), err))?;
+        let y = y
+            .parse::<u16>()
+            .map_err(|err| MapTileDescriptorParseError::Y(s.to_owned(), err))?;
+        let grid_coordinates = GridCoordinates::new(x, y);
+        let map_tile_descriptor = MapTileDescriptor::new(zoom_level, grid_coordinates);
+        if map_tile_descriptor.lower_left_corner != grid_coordinates {
+            return Err(MapTileDescriptorParseError::NotLowerLeftCorner(
+                s.to_owned(),
+            ));
+        }
+        Ok(map_tile_descriptor.with_layer(layer))
+    }
+}
+
 /// A waypoint in the Universal Sailor Buddy (USB) notecard format
 #[derive(Debug, Clone)]
 pub struct USBWaypoint {
@@ -828,9 +1175,11 @@ impl std::str::FromStr for USBWaypoint {
     type Err = LocationParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // hand-edited notecards often contain indented lines or trailing spaces
+        let s = s.trim();
         if let Some((location, comment)) = s.split_once(',') {
             Ok(Self {
-                location: location.parse()?,
+                location: location.trim_end().parse()?,
                 comment: Some(comment.to_owned()),
             })
         } else {
@@ -867,6 +1216,24 @@ impl USBNotecard {
         Self { waypoints }
     }
 
+    /// Create a new USB notecard from waypoints
+    #[must_use]
+    pub fn from_waypoints(waypoints: impl IntoIterator<Item = USBWaypoint>) -> Self {
+        Self {
+            waypoints: waypoints.into_iter().collect(),
+        }
+    }
+
+    /// Create a new USB notecard from locations, the waypoints have no comments
+    #[must_use]
+    pub fn from_locations(locations: impl IntoIterator<Item = Location>) -> Self {
+        Self::from_waypoints(
+            locations
+                .into_iter()
+                .map(|location| USBWaypoint::new(location, None)),
+        )
+    }
+
     /// get the waypoints in the notecard
     #[must_use]
     pub fn waypoints(&self) -> &[USBWaypoint] {
@@ -883,6 +1250,190 @@ impl USBNotecard {
         let contents = std::fs::read_to_string(filename)?;
         Ok(contents.parse()?)
     }
+
+    /// the positions of the waypoints in meters from the south west corner
+    /// of the grid
+    ///
+    /// returns `None` if one of the region names can not be resolved
+    fn grid_positions<F>(&self, mut region_grid_coordinates: F) -> Option<Vec<[f64; 3]>>
+    where
+        F: FnMut(&RegionName) -> Option<GridCoordinates>,
+    {
+        self.waypoints
+            .iter()
+            .map(|waypoint| {
+                let grid_coordinates = region_grid_coordinates(waypoint.location().region_name())?;
+                Some(grid_position(
+                    &grid_coordinates,
+                    &waypoint.region_coordinates(),
+                ))
+            })
+            .collect()
+    }
+
+    /// the position at the given fraction (0.0 to 1.0) of the total length
+    /// of the route, e.g. for animating a marker travelling along the route
+    ///
+    /// the length of the route is measured horizontally along the straight
+    /// lines between the waypoints, the height is interpolated linearly
+    ///
+    /// `region_grid_coordinates` is used to resolve the region names of the
+    /// waypoints to `GridCoordinates`, e.g. from a previously filled cache
+    ///
+    /// returns `None` if the fraction is out of range, the notecard has
+    /// no waypoints or one of the region names can not be resolved
+    #[must_use]
+    pub fn interpolate<F>(
+        &self,
+        fraction: f64,
+        region_grid_coordinates: F,
+    ) -> Option<(GridCoordinates, RegionCoordinates)>
+    where
+        F: FnMut(&RegionName) -> Option<GridCoordinates>,
+    {
+        if !(0f64..=1f64).contains(&fraction) {
+            return None;
+        }
+        let positions = self.grid_positions(region_grid_coordinates)?;
+        let last = *positions.last()?;
+        let total_length: f64 = positions
+            .windows(2)
+            .map(|leg| horizontal_distance(&leg[0], &leg[1]))
+            .sum();
+        let mut remaining_length = fraction * total_length;
+        for leg in positions.windows(2) {
+            let leg_length = horizontal_distance(&leg[0], &leg[1]);
+            if leg_length > 0f64 && remaining_length <= leg_length {
+                let leg_fraction = remaining_length / leg_length;
+                return Some(from_grid_position(&[
+                    leg[0][0] + (leg[1][0] - leg[0][0]) * leg_fraction,
+                    leg[0][1] + (leg[1][1] - leg[0][1]) * leg_fraction,
+                    leg[0][2] + (leg[1][2] - leg[0][2]) * leg_fraction,
+                ]));
+            }
+            remaining_length -= leg_length;
+        }
+        Some(from_grid_position(&last))
+    }
+
+    /// the waypoint closest to the given `Location` and its horizontal
+    /// distance in meters
+    ///
+    /// `region_grid_coordinates` is used to resolve the region names to
+    /// `GridCoordinates` like in `USBNotecard::interpolate`
+    ///
+    /// returns `None` if the notecard has no waypoints or one of the region
+    /// names can not be resolved
+    #[must_use]
+    pub fn nearest_waypoint<F>(
+        &self,
+        to: &Location,
+        mut region_grid_coordinates: F,
+    ) -> Option<(&USBWaypoint, f64)>
+    where
+        F: FnMut(&RegionName) -> Option<GridCoordinates>,
+    {
+        let to = grid_position(
+            &region_grid_coordinates(to.region_name())?,
+            &RegionCoordinates::new(f32::from(to.x()), f32::from(to.y()), f32::from(to.z())),
+        );
+        let positions = self.grid_positions(region_grid_coordinates)?;
+        self.waypoints
+            .iter()
+            .zip(positions.iter())
+            .map(|(waypoint, position)| (waypoint, horizontal_distance(position, &to)))
+            .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+    }
+
+    /// the point on the route (the straight lines between the waypoints)
+    /// closest to the given `Location` and its horizontal distance in meters,
+    /// e.g. to snap a current position to the route
+    ///
+    /// `region_grid_coordinates` is used to resolve the region names to
+    /// `GridCoordinates` like in `USBNotecard::interpolate`
+    ///
+    /// returns `None` if the notecard has no waypoints or one of the region
+    /// names can not be resolved
+    #[must_use]
+    pub fn nearest_point_on_route<F>(
+        &self,
+        to: &Location,
+        mut region_grid_coordinates: F,
+    ) -> Option<((GridCoordinates, RegionCoordinates), f64)>
+    where
+        F: FnMut(&RegionName) -> Option<GridCoordinates>,
+    {
+        let to = grid_position(
+            &region_grid_coordinates(to.region_name())?,
+            &RegionCoordinates::new(f32::from(to.x()), f32::from(to.y()), f32::from(to.z())),
+        );
+        let positions = self.grid_positions(region_grid_coordinates)?;
+        let first = *positions.first()?;
+        let mut nearest = (first, horizontal_distance(&first, &to));
+        for leg in positions.windows(2) {
+            let (delta_x, delta_y) = (leg[1][0] - leg[0][0], leg[1][1] - leg[0][1]);
+            let leg_length_squared = delta_x * delta_x + delta_y * delta_y;
+            if leg_length_squared <= 0f64 {
+                continue;
+            }
+            let leg_fraction = (((to[0] - leg[0][0]) * delta_x + (to[1] - leg[0][1]) * delta_y)
+                / leg_length_squared)
+                .clamp(0f64, 1f64);
+            let point = [
+                leg[0][0] + delta_x * leg_fraction,
+                leg[0][1] + delta_y * leg_fraction,
+                leg[0][2] + (leg[1][2] - leg[0][2]) * leg_fraction,
+            ];
+            let distance = horizontal_distance(&point, &to);
+            if distance < nearest.1 {
+                nearest = (point, distance);
+            }
+        }
+        Some((from_grid_position(&nearest.0), nearest.1))
+    }
+}
+
+/// the position in meters from the south west corner of the grid for the
+/// given `GridCoordinates` and `RegionCoordinates`
+#[must_use]
+fn grid_position(
+    grid_coordinates: &GridCoordinates,
+    region_coordinates: &RegionCoordinates,
+) -> [f64; 3] {
+    [
+        f64::from(grid_coordinates.x()) * 256f64 + f64::from(region_coordinates.x()),
+        f64::from(grid_coordinates.y()) * 256f64 + f64::from(region_coordinates.y()),
+        f64::from(region_coordinates.z()),
+    ]
+}
+
+/// the `GridCoordinates` and `RegionCoordinates` for a position in meters
+/// from the south west corner of the grid, the inverse of `grid_position`
+#[must_use]
+fn from_grid_position(position: &[f64; 3]) -> (GridCoordinates, RegionCoordinates) {
+    let grid_x = (position[0] / 256f64).floor();
+    let grid_y = (position[1] / 256f64).floor();
+    (
+        GridCoordinates::new(grid_x as u16, grid_y as u16),
+        RegionCoordinates::new(
+            (position[0] - grid_x * 256f64) as f32,
+            (position[1] - grid_y * 256f64) as f32,
+            position[2] as f32,
+        ),
+    )
+}
+
+/// the horizontal distance in meters between two positions returned by
+/// `grid_position`
+#[must_use]
+fn horizontal_distance(from: &[f64; 3], to: &[f64; 3]) -> f64 {
+    (to[0] - from[0]).hypot(to[1] - from[1])
+}
+
+impl Extend<USBWaypoint> for USBNotecard {
+    fn extend<T: IntoIterator<Item = USBWaypoint>>(&mut self, iter: T) {
+        self.waypoints.extend(iter);
+    }
 }
 
 impl std::fmt::Display for USBNotecard {
@@ -952,6 +1503,159 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_usb_waypoint_surrounding_whitespace() -> Result<(), Box<dyn std::error::Error>> {
+        let usb_notecard =
+            "  Thorkell/10/20/30\nThorkell/40/50/60   \n\tThorkell/70/80/90 ,comment"
+                .parse::<USBNotecard>()?;
+        let locations = usb_notecard
+            .waypoints()
+            .iter()
+            .map(|waypoint| waypoint.location().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations,
+            vec![
+                "Thorkell/10/20/30".parse::<Location>()?,
+                "Thorkell/40/50/60".parse::<Location>()?,
+                "Thorkell/70/80/90".parse::<Location>()?,
+            ]
+        );
+        assert_eq!(
+            usb_notecard.waypoints()[2].comment(),
+            Some(&"comment".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_usb_notecard_from_locations() -> Result<(), Box<dyn std::error::Error>> {
+        let mut usb_notecard = USBNotecard::from_locations(vec![
+            Location::new(RegionName::try_new("Thorkell")?, 10, 20, 30),
+            Location::new(RegionName::try_new("Beach Valley")?, 40, 50, 60),
+        ]);
+        usb_notecard.extend([USBWaypoint::new(
+            Location::new(RegionName::try_new("Ahern")?, 70, 80, 90),
+            Some("finish".to_string()),
+        )]);
+        assert_eq!(
+            usb_notecard.to_string(),
+            "https://maps.secondlife.com/secondlife/Thorkell/10/20/30\n\
+             https://maps.secondlife.com/secondlife/Beach%20Valley/40/50/60\n\
+             https://maps.secondlife.com/secondlife/Ahern/70/80/90,finish\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_usb_notecard_interpolate() -> Result<(), Box<dyn std::error::Error>> {
+        let usb_notecard =
+            "Thorkell/10/10/20\nThorkell/110/10/20\nThorkell/110/110/40".parse::<USBNotecard>()?;
+        let region_grid_coordinates = |_: &RegionName| Some(GridCoordinates::new(1136, 1075));
+        assert_eq!(
+            usb_notecard.interpolate(0f64, region_grid_coordinates),
+            Some((
+                GridCoordinates::new(1136, 1075),
+                RegionCoordinates::new(10f32, 10f32, 20f32)
+            ))
+        );
+        assert_eq!(
+            usb_notecard.interpolate(0.5f64, region_grid_coordinates),
+            Some((
+                GridCoordinates::new(1136, 1075),
+                RegionCoordinates::new(110f32, 10f32, 20f32)
+            ))
+        );
+        assert_eq!(
+            usb_notecard.interpolate(0.75f64, region_grid_coordinates),
+            Some((
+                GridCoordinates::new(1136, 1075),
+                RegionCoordinates::new(110f32, 60f32, 30f32)
+            ))
+        );
+        assert_eq!(
+            usb_notecard.interpolate(1f64, region_grid_coordinates),
+            Some((
+                GridCoordinates::new(1136, 1075),
+                RegionCoordinates::new(110f32, 110f32, 40f32)
+            ))
+        );
+        assert_eq!(
+            usb_notecard.interpolate(1.5f64, region_grid_coordinates),
+            None
+        );
+        assert_eq!(usb_notecard.interpolate(0.5f64, |_| None), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_usb_notecard_interpolate_across_regions() -> Result<(), Box<dyn std::error::Error>> {
+        let usb_notecard = "Thorkell/200/128/0\nAhern/56/128/0".parse::<USBNotecard>()?;
+        let region_grid_coordinates =
+            |region_name: &RegionName| match region_name.to_string().as_str() {
+                "Thorkell" => Some(GridCoordinates::new(1136, 1075)),
+                "Ahern" => Some(GridCoordinates::new(1137, 1075)),
+                _ => None,
+            };
+        assert_eq!(
+            usb_notecard.interpolate(0.75f64, region_grid_coordinates),
+            Some((
+                GridCoordinates::new(1137, 1075),
+                RegionCoordinates::new(28f32, 128f32, 0f32)
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_usb_notecard_nearest_waypoint() -> Result<(), Box<dyn std::error::Error>> {
+        let usb_notecard =
+            "Thorkell/10/10/20\nThorkell/110/10/20\nThorkell/110/110/40".parse::<USBNotecard>()?;
+        let region_grid_coordinates = |_: &RegionName| Some(GridCoordinates::new(1136, 1075));
+        let to = "Thorkell/100/30/0".parse::<Location>()?;
+        let Some((waypoint, distance)) =
+            usb_notecard.nearest_waypoint(&to, region_grid_coordinates)
+        else {
+            panic!("expected a nearest waypoint");
+        };
+        assert_eq!(
+            waypoint.location(),
+            &"Thorkell/110/10/20".parse::<Location>()?
+        );
+        assert!((distance - 500f64.sqrt()).abs() < 0.001f64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_usb_notecard_nearest_point_on_route() -> Result<(), Box<dyn std::error::Error>> {
+        let usb_notecard =
+            "Thorkell/10/10/20\nThorkell/110/10/20\nThorkell/110/110/40".parse::<USBNotecard>()?;
+        let region_grid_coordinates = |_: &RegionName| Some(GridCoordinates::new(1136, 1075));
+        let to = "Thorkell/60/30/0".parse::<Location>()?;
+        assert_eq!(
+            usb_notecard.nearest_point_on_route(&to, region_grid_coordinates),
+            Some((
+                (
+                    GridCoordinates::new(1136, 1075),
+                    RegionCoordinates::new(60f32, 10f32, 20f32)
+                ),
+                20f64
+            ))
+        );
+        let to = "Thorkell/130/60/0".parse::<Location>()?;
+        assert_eq!(
+            usb_notecard.nearest_point_on_route(&to, region_grid_coordinates),
+            Some((
+                (
+                    GridCoordinates::new(1136, 1075),
+                    RegionCoordinates::new(110f32, 60f32, 30f32)
+                ),
+                20f64
+            ))
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_parse_location_bare_with_usb_comment() -> Result<(), Box<dyn std::error::Error>> {
         assert_eq!(
@@ -966,6 +1670,172 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_region_name_valid_candidates() {
+        assert!(RegionName::is_valid_candidate("Da Boom"));
+        assert!(RegionName::is_valid_candidate("Beach Valley"));
+        assert!(RegionName::is_valid_candidate("  Thorkell  "));
+        assert!(RegionName::is_valid_candidate("O'Hare"));
+        assert!(RegionName::is_valid_candidate("Bay City - Alley"));
+    }
+
+    #[test]
+    fn test_region_name_disallowed_characters() {
+        assert!(!RegionName::is_valid_candidate("Beach/Valley"));
+        assert!(!RegionName::is_valid_candidate("Beach,Valley"));
+        assert!(!RegionName::is_valid_candidate("Beach%20Valley"));
+        assert!(!RegionName::is_valid_candidate("Beach?Valley"));
+        assert!(!RegionName::is_valid_candidate("Beach\tValley"));
+        assert!(!RegionName::is_valid_candidate("<Beach Valley>"));
+        assert!(!RegionName::is_valid_candidate("-Beach Valley"));
+        assert!(!RegionName::is_valid_candidate("     "));
+    }
+
+    #[test]
+    fn test_region_name_try_new_only_checks_length() {
+        assert!(RegionName::try_new("Beach,Valley").is_ok());
+        assert!(RegionName::try_new("Région Über").is_ok());
+    }
+
+    #[test]
+    fn test_parse_location_region_name_errors() {
+        assert_eq!(
+            "%20%20/10/20/30"
+                .parse::<Location>()
+                .err()
+                .as_ref()
+                .and_then(LocationParseError::region_name_error),
+            Some(&RegionNameError::LenCharMinViolated)
+        );
+        assert_eq!(
+            "Ab/10/20/30"
+                .parse::<Location>()
+                .err()
+                .as_ref()
+                .and_then(LocationParseError::region_name_error),
+            Some(&RegionNameError::LenCharMinViolated)
+        );
+        assert_eq!(
+            "Abcdefghijabcdefghijabcdefghijabcdef/10/20/30"
+                .parse::<Location>()
+                .err()
+                .as_ref()
+                .and_then(LocationParseError::region_name_error),
+            Some(&RegionNameError::LenCharMaxViolated)
+        );
+        assert_eq!(
+            "Thorkell/10/20/foo"
+                .parse::<Location>()
+                .err()
+                .as_ref()
+                .and_then(LocationParseError::region_name_error),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_location_bare_encoded_apostrophe() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            "O%27Hare/10/20/30".parse::<Location>(),
+            Ok(Location {
+                region_name: RegionName::try_new("O'Hare")?,
+                x: 10,
+                y: 20,
+                z: 30
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_location_url_encoded_apostrophe() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            "http://maps.secondlife.com/secondlife/O%27Hare%20Bay/10/20/30".parse::<Location>(),
+            Ok(Location {
+                region_name: RegionName::try_new("O'Hare Bay")?,
+                x: 10,
+                y: 20,
+                z: 30
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_location() -> Result<(), Box<dyn std::error::Error>> {
+        let location = Location::new(RegionName::try_new("Beach Valley")?, 110, 67, 24);
+        assert_eq!(location.to_string(), "Beach%20Valley/110/67/24");
+        Ok(())
+    }
+
+    #[test]
+    fn test_location_as_slurl() -> Result<(), Box<dyn std::error::Error>> {
+        let location = Location::new(RegionName::try_new("Beach Valley")?, 110, 67, 24);
+        assert_eq!(
+            location.as_slurl(),
+            "http://slurl.com/secondlife/Beach%20Valley/110/67/24"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_location_as_app_teleport_uri() -> Result<(), Box<dyn std::error::Error>> {
+        let location = Location::new(RegionName::try_new("Beach Valley")?, 110, 67, 24);
+        assert_eq!(
+            location.as_app_teleport_uri(),
+            "secondlife:///app/teleport/Beach%20Valley/110/67/24"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_location_as_maps_url() -> Result<(), Box<dyn std::error::Error>> {
+        let location = Location::new(RegionName::try_new("Thorkell")?, 1, 2, 3);
+        assert_eq!(
+            location.as_maps_url(),
+            "https://maps.secondlife.com/secondlife/Thorkell/1/2/3"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_grid_coordinates_is_plausible_region() {
+        assert!(GridCoordinates::new(1000, 1000).is_plausible_region());
+        assert!(GridCoordinates::new(1136, 1075).is_plausible_region());
+        assert!(GridCoordinates::new(395, 479).is_plausible_region());
+        assert!(GridCoordinates::new(1358, 1430).is_plausible_region());
+        assert!(!GridCoordinates::new(0, 0).is_plausible_region());
+        assert!(!GridCoordinates::new(1136, 5000).is_plausible_region());
+        assert!(!GridCoordinates::new(60000, 1075).is_plausible_region());
+    }
+
+    #[test]
+    fn test_grid_coordinates_center_and_corner_location() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let grid_coordinates = GridCoordinates::new(1136, 1075);
+        let region_name = RegionName::try_new("Thorkell")?;
+        assert_eq!(
+            grid_coordinates.center_location(region_name.clone()),
+            Location::new(region_name.clone(), 128, 128, 0)
+        );
+        assert_eq!(
+            grid_coordinates.corner_location(region_name.clone()),
+            Location::new(region_name, 0, 0, 0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_location_as_maps_url_encoded() -> Result<(), Box<dyn std::error::Error>> {
+        let location = Location::new(RegionName::try_new("O'Hare Beach")?, 1, 2, 3);
+        assert_eq!(
+            location.as_maps_url(),
+            "https://maps.secondlife.com/secondlife/O%27Hare%20Beach/1/2/3"
+        );
+        assert_eq!(location.as_maps_url().parse::<Location>()?, location);
+        Ok(())
+    }
+
     #[test]
     fn test_grid_rectangle_intersection_upper_right_corner(
     ) -> Result<(), Box<dyn std::error::Error>> {
@@ -1033,4 +1903,164 @@ mod test {
         assert_eq!(rect1.intersect(&rect2), None);
         Ok(())
     }
+
+    #[test]
+    fn test_zoom_level_display_from_str() -> Result<(), Box<dyn std::error::Error>> {
+        for zoom_level in 1..=8 {
+            let zoom_level = ZoomLevel::try_new(zoom_level)?;
+            // the map tile file names rely on the bare integer here
+            assert_eq!(zoom_level.to_string(), zoom_level.into_inner().to_string());
+            assert_eq!(zoom_level.to_string().parse::<ZoomLevel>()?, zoom_level);
+            let map_tile_descriptor =
+                MapTileDescriptor::new(zoom_level, GridCoordinates::new(1136, 1072));
+            assert_eq!(
+                map_tile_descriptor
+                    .tile_filename()
+                    .parse::<MapTileDescriptor>()?
+                    .zoom_level(),
+                &zoom_level
+            );
+        }
+        assert!("0".parse::<ZoomLevel>().is_err());
+        assert!("9".parse::<ZoomLevel>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_tile_descriptor_display() -> Result<(), Box<dyn std::error::Error>> {
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(3)?, GridCoordinates::new(1137, 1075));
+        assert_eq!(map_tile_descriptor.to_string(), "map-3-1136-1072");
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_tile_descriptor_tile_filename() -> Result<(), Box<dyn std::error::Error>> {
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert_eq!(
+            map_tile_descriptor.tile_filename(),
+            "map-1-1136-1075-objects.jpg"
+        );
+        assert_eq!(
+            map_tile_descriptor
+                .tile_filename()
+                .parse::<MapTileDescriptor>()?,
+            map_tile_descriptor
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_tile_descriptor_layer_tile_filename() -> Result<(), Box<dyn std::error::Error>> {
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        assert_eq!(map_tile_descriptor.layer(), MapLayer::Objects);
+        for (layer, file_name) in [
+            (MapLayer::Objects, "map-1-1136-1075-objects.jpg"),
+            (MapLayer::Terrain, "map-1-1136-1075-terrain.jpg"),
+            (MapLayer::LandForSale, "map-1-1136-1075-land-for-sale.jpg"),
+        ] {
+            let map_tile_descriptor = map_tile_descriptor.clone().with_layer(layer);
+            assert_eq!(map_tile_descriptor.tile_filename(), file_name);
+            assert_eq!(file_name.parse::<MapTileDescriptor>()?, map_tile_descriptor);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_tile_descriptor_file_name_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        for zoom_level in 1..=8 {
+            let map_tile_descriptor = MapTileDescriptor::new(
+                ZoomLevel::try_new(zoom_level)?,
+                GridCoordinates::new(1137, 1075),
+            );
+            assert_eq!(
+                map_tile_descriptor
+                    .to_string()
+                    .parse::<MapTileDescriptor>()?,
+                map_tile_descriptor
+            );
+            assert_eq!(
+                format!("{map_tile_descriptor}-objects.jpg").parse::<MapTileDescriptor>()?,
+                map_tile_descriptor
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_map_tile_descriptor_invalid() {
+        assert!(matches!(
+            "map-1-1136".parse::<MapTileDescriptor>(),
+            Err(MapTileDescriptorParseError::UnexpectedFormat(_))
+        ));
+        assert!(matches!(
+            "tile-1-1136-1075".parse::<MapTileDescriptor>(),
+            Err(MapTileDescriptorParseError::UnexpectedFormat(_))
+        ));
+        assert!(matches!(
+            "map-9-1136-1075".parse::<MapTileDescriptor>(),
+            Err(MapTileDescriptorParseError::InvalidZoomLevel(_, _))
+        ));
+        assert!(matches!(
+            "map-2-1137-1075".parse::<MapTileDescriptor>(),
+            Err(MapTileDescriptorParseError::NotLowerLeftCorner(_))
+        ));
+    }
+
+    #[test]
+    fn test_map_tile_descriptor_serde_format() -> Result<(), Box<dyn std::error::Error>> {
+        let map_tile_descriptor =
+            MapTileDescriptor::new(ZoomLevel::try_new(1)?, GridCoordinates::new(1136, 1075));
+        let json = r#"{"zoom_level":1,"lower_left_corner":{"x":1136,"y":1075}}"#;
+        assert_eq!(serde_json::to_string(&map_tile_descriptor)?, json);
+        assert_eq!(
+            serde_json::from_str::<MapTileDescriptor>(json)?,
+            map_tile_descriptor
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_region_coordinates_snapped_to_meter() {
+        assert_eq!(
+            RegionCoordinates::new(10.9, 0.1, 25.5).snapped_to_meter(),
+            (10, 0, 25)
+        );
+        assert_eq!(
+            RegionCoordinates::new(-0.5, 255.999, f32::NAN).snapped_to_meter(),
+            (0, 255, 0)
+        );
+    }
+
+    #[test]
+    fn test_region_coordinates_key_bucketing() {
+        let mut buckets = std::collections::HashMap::new();
+        for region_coordinates in [
+            RegionCoordinates::new(10.1, 20.2, 30.3),
+            RegionCoordinates::new(10.9, 20.8, 30.0),
+            RegionCoordinates::new(11.0, 20.5, 30.5),
+        ] {
+            *buckets
+                .entry(RegionCoordinatesKey::from(&region_coordinates))
+                .or_insert(0) += 1;
+        }
+        assert_eq!(buckets.len(), 2);
+        let key = RegionCoordinatesKey::from(&RegionCoordinates::new(10.0, 20.0, 30.0));
+        assert_eq!(buckets.get(&key), Some(&2));
+        assert_eq!(
+            RegionCoordinates::from(key),
+            RegionCoordinates::new(10.0, 20.0, 30.0)
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_location_url_round_trip(location in proptest::prelude::any::<Location>()) {
+            proptest::prop_assert_eq!(location.as_maps_url().parse::<Location>(), Ok(location.clone()));
+            proptest::prop_assert_eq!(location.as_slurl().parse::<Location>(), Ok(location.clone()));
+            proptest::prop_assert_eq!(location.to_string().parse::<Location>(), Ok(location));
+        }
+    }
 }
@@ -0,0 +1,18 @@
+//! Re-exports of the commonly used types
+//!
+//! ```
+//! use sl_types::prelude::*;
+//!
+//! let location: Location = "Thorkell/128/128/20".parse().unwrap();
+//! let grid_coordinates = GridCoordinates::new(1136, 1075);
+//! let grid_rectangle = GridRectangle::new(grid_coordinates, grid_coordinates);
+//! assert_eq!(location.region_name(), &RegionName::try_new("Thorkell").unwrap());
+//! assert_eq!(grid_rectangle.size_x(), 1);
+//! assert!(ZoomLevel::try_new(1).is_ok());
+//! ```
+
+pub use crate::map::{
+    GridCoordinateOffset, GridCoordinates, GridCoordinatesExt, GridRectangle, GridRectangleLike,
+    Location, MapLayer, MapTileDescriptor, RegionCoordinates, RegionName, USBNotecard, USBWaypoint,
+    ZoomLevel,
+};